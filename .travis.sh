@@ -24,7 +24,7 @@ channel() {
 
 run_test() {
   cargo clean
-  channel test -v
+  channel test -v --all-features
 }
 
 run_clippy() {
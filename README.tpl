@@ -13,9 +13,9 @@ actually works pretty well against a wide range of diesel versions. It is only
 explicitly tested against the newest Diesel version. It is currently known to
 work at least with Diesel versions 0.14 - 0.16.
 
-It will always support the lowest version of Rust that Diesel supports. Since
-at least diesel 0.14 that's `1.18.0`. Requiring a new Diesel or Rust version
-will always at least bump the minor version.
+It requires at least Rust `1.71.0`, which is what the current `syn`, `quote`
+and `proc-macro2` releases need. Requiring a new Diesel or Rust version will
+always at least bump the minor version.
 
 ## License
 
@@ -27,13 +27,89 @@
 //!     `from_new_struct` static method.
 //!   * `#[intermediate_exclude(SomePrefix)]` excludes from the `NewStruct`
 //!     generated struct, but causes a `SomePrefixStruct` to be generated,
-//!     which *will* have this field.
+//!     which *will* have this field. A field can belong to several
+//!     intermediates with `#[intermediate_exclude(SomePrefix, OtherPrefix)]`,
+//!     and there are `from_*` constructors between every pair of
+//!     intermediates where the target has fields that the source doesn't.
+//!   * Either form can be given a `default`, e.g.
+//!     `#[intermediate_exclude(default = "Utc::now()")]`, or just
+//!     `#[intermediate_exclude(default)]` for `Default::default()`. The
+//!     `from_*` constructors fill defaulted fields in themselves instead of
+//!     taking them as parameters.
+//! * The `#[intermediate_only(Name)]` field-level attribute is the other way
+//!   around: it generates a `NameStruct` that has *only* the fields marked
+//!   with it, which is handy for narrow projections of wide tables. It gets
+//!   the same `to_*` projections and `from_*` constructors as the others.
+//! * The `#[intermediates(...)]` struct-level attribute declares the same
+//!   thing in one place instead of on each field:
+//!   `#[intermediates(New(exclude(id, created_at)), Captured(exclude(id)))]`
+//!   is the same as `#[intermediate_exclude]` on `id` and
+//!   `#[intermediate_exclude(Captured)]` on `created_at`, and
+//!   `Summary(include(id, name))` is the same as `#[intermediate_only(Summary)]`
+//!   on both fields. Tuple struct fields are named by position, e.g.
+//!   `exclude(0)`.
+//! * The `#[intermediate_field_attr(Name, attr)]` field-level attribute puts
+//!   `#[attr]` on the field in the `Name` intermediate only, e.g.
+//!   `#[intermediate_field_attr(New, serde(default))]`. Every other
+//!   attribute on a field is copied to all of the intermediates that have it.
 //! * The `#[intermediate_derive(Traits...)]` struct-level attribute applies
-//!   its contained traits to all the intermediate structs generated.
+//!   its contained traits to all the intermediate structs generated. Traits
+//!   can be limited to a single intermediate by naming it, e.g.
+//!   `#[intermediate_derive(Debug, New: Deserialize, Captured: Queryable + Eq)]`
+//!   derives `Debug` everywhere but `Deserialize` only on `NewStruct`.
+//! * The `#[intermediate_names(...)]` struct-level attribute changes how the
+//!   intermediates are named: `#[intermediate_names(suffix)]` generates
+//!   `StructNew` and `StructSomePrefix` instead, and
+//!   `#[intermediate_names(New = "StructInsert")]` picks the name of a single
+//!   intermediate. The `from_*` constructors follow the same names, e.g.
+//!   `from_struct_insert`.
+//! * The `#[intermediate_constructor(New = "persisted")]` struct-level
+//!   attribute renames the constructors that build from an intermediate, so
+//!   that `Struct::from_new_struct(id, new)` becomes
+//!   `Struct::persisted(id, new)`. Every other intermediate that can be built
+//!   from `NewStruct` gets a `persisted` constructor too.
+//! * The `#[intermediate_ref]` struct-level attribute adds a
+//!   `NewStructRef<'a>` intermediate with the fields of `NewStruct`, each
+//!   borrowed, so that inserting doesn't need to clone anything. It gets the
+//!   same derives and table name as `NewStruct`, and is built from a
+//!   `&Struct` or a `&NewStruct` with `From` or `to_new_struct_ref()`.
+//! * The `#[intermediate_builder]` struct-level attribute adds
+//!   `NewStruct::builder()`, which returns a `NewStructBuilder` with a setter
+//!   for every field of `NewStruct`. Its `build()` returns an `Err` naming
+//!   the first field that was never set. Builders are only generated for
+//!   structs.
+//! * The `#[intermediate_changeset(Patch)]` struct-level attribute adds a
+//!   `PatchStruct` intermediate that derives `AsChangeset`. It has the
+//!   fields of `NewStruct`, each wrapped in an `Option` so that only the ones
+//!   that are set get updated, and a `patch.apply(&mut full)` that updates an
+//!   in-memory `Struct` the same way. It can be given derives, a table name
+//!   and a name like the other intermediates.
+//! * The `#[intermediate_attr(attr)]` struct-level attribute puts `#[attr]`
+//!   on every intermediate, e.g.
+//!   `#[intermediate_attr(serde(rename_all = "camelCase"))]`, and
+//!   `#[intermediate_attr(New, serde(deny_unknown_fields))]` puts it on
+//!   `NewStruct` only. Other attributes on the struct are not copied.
 //! * `DieselIntermediate` will apply diesel's `#[table_name = "..."]`
-//!   struct-level attribute to all generated structs, if you need to use a
-//!   different table name you can use `#[intermediate_table_name = "..."]` to
-//!   override the default.
+//!   struct-level attribute to every generated struct that derives a diesel
+//!   trait which reads it, if you need to use a different table name you can
+//!   use `#[intermediate_table_name = "..."]` to override the default. A
+//!   single intermediate can be given its own table with
+//!   `#[intermediate_table_name(Captured = "captured_rusts")]`, the others
+//!   keep using the default.
+//! * When `NewStruct` has a table name it derives diesel's `Insertable`
+//!   without having to list it in `#[intermediate_derive]`. Other insert
+//!   shapes can be listed with `#[intermediate_insertable(New, Captured)]`,
+//!   which replaces the default, and `#[intermediate_insertable()]` turns it
+//!   off.
+//! * The `#[intermediate_insert(backend = "sqlite")]` struct-level attribute
+//!   adds `NewStruct::insert(self, &conn)` to every insertable intermediate,
+//!   which inserts it and returns the full `Struct` with everything the
+//!   database filled in. The backend is `"pg"`, `"sqlite"` or `"mysql"`, and
+//!   diesel needs the matching cargo feature. Postgres uses `RETURNING`.
+//!   Diesel 1 can't do that for SQLite and MySQL, so they look the row up
+//!   again in a transaction: by the primary key (diesel's `#[primary_key]`, or
+//!   `id`) if the intermediate has it, otherwise by `last_insert_rowid()` on
+//!   SQLite and by the `AUTO_INCREMENT` `LAST_INSERT_ID()` on MySQL.
 //!
 //! # Example
 //!
@@ -119,15 +195,76 @@
 //!         }
 //!     }
 //! }
+//!
+//! // Every constructor is also available as a `From` impl taking the same
+//! // arguments as a tuple, so `(id, captured).into()` works too.
+//! impl From<(i32, CapturedRust)> for Rust {
+//!     fn from((id, base): (i32, CapturedRust)) -> Self {
+//!         Rust::from_captured_rust(id, base)
+//!     }
+//! }
+//!
+//! impl From<(i32, i32, NewRust)> for Rust {
+//!     fn from((id, mycologist_id, base): (i32, i32, NewRust)) -> Self {
+//!         Rust::from_new_rust(id, mycologist_id, base)
+//!     }
+//! }
+//!
+//! // Projections back down to every intermediate, dropping the excluded
+//! // fields. The borrowing ones clone, so they can only be called if the
+//! // fields are `Clone`. Intermediates get the same projections down to
+//! // the intermediates they contain, e.g. `CapturedRust::to_new_rust`.
+//! impl Rust {
+//!     pub fn to_new_rust(&self) -> NewRust {
+//!         NewRust {
+//!             life_cycle_stage: self.life_cycle_stage.clone(),
+//!         }
+//!     }
+//!
+//!     pub fn to_captured_rust(&self) -> CapturedRust {
+//!         CapturedRust {
+//!             mycologist_id: self.mycologist_id.clone(),
+//!             life_cycle_stage: self.life_cycle_stage.clone(),
+//!         }
+//!     }
+//! }
+//!
+//! impl From<Rust> for NewRust {
+//!     fn from(base: Rust) -> Self {
+//!         NewRust {
+//!             life_cycle_stage: base.life_cycle_stage,
+//!         }
+//!     }
+//! }
+//!
+//! impl From<Rust> for CapturedRust {
+//!     fn from(base: Rust) -> Self {
+//!         CapturedRust {
+//!             mycologist_id: base.mycologist_id,
+//!             life_cycle_stage: base.life_cycle_stage,
+//!         }
+//!     }
+//! }
 //! ```
 //!
 //! see [`tests/diesel-interaction.rs`](tests/diesel-interaction.rs) for a
 //! couple fully-worked examples, including using with `Insertable` and the
 //! purpose of the `intermediate_exclude(NAME)` form.
 //!
-//! Interestingly, since this is abusing the derive proc-macro infrastructure,
-//! if you have no `#[intermediate_derive(...)]` attributes, you will get
-//! "empty trait list in \`derive\`" warnings.
+//! Tuple structs work the same way: every intermediate is a tuple struct with
+//! the remaining fields in their original order, and the `from_*` constructors
+//! take the missing fields positionally. Unit structs get unit intermediates.
+//!
+//! Enums are handled variant by variant: `NewEvent` has every variant of
+//! `Event`, each without its excluded fields. Each variant gets its own
+//! constructor which only takes the fields missing from that variant, e.g.
+//! `Event::from_new_event_found(id, base)`. It returns `Err(base)` if `base`
+//! is a different variant. Enums don't get the `From` impls.
+//!
+//! Generic and borrowing structs are supported. Each intermediate only keeps
+//! the generic parameters (and their bounds) that its own fields use, so
+//! `NewPost` for a `Post<'a, Id>` whose only `Id` field is excluded is just
+//! `NewPost<'a>`.
 //!
 //! ## Limitations
 //!
@@ -139,202 +276,641 @@
 
 extern crate proc_macro;
 
-extern crate syn;
+use std::collections::HashSet;
 
-extern crate heck;
-#[macro_use]
-extern crate quote;
-
-use std::collections::{HashMap, HashSet};
-use std::iter::FromIterator;
-
-use heck::SnakeCase;
-use proc_macro::TokenStream;
-use quote::Tokens;
-use syn::{Attribute, Body, DeriveInput, Field, Ident, MetaItem, NestedMetaItem, Visibility};
+use heck::ToSnakeCase;
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DataEnum, DeriveInput, Expr, ExprLit, Field,
+    Fields, GenericParam, Generics, Ident, Lifetime, LifetimeParam, Lit, LitStr, Member, Meta,
+    MetaList, MetaNameValue, Path, Token, Type, Visibility, WhereClause,
+};
 
 const EXCLUDE: &str = "intermediate_exclude";
 const DERIVE: &str = "intermediate_derive";
 const OVERRIDE_TABLE_NAME: &str = "intermediate_table_name";
+const NAMES: &str = "intermediate_names";
+const CONSTRUCTOR: &str = "intermediate_constructor";
+const REF: &str = "intermediate_ref";
+const BUILDER: &str = "intermediate_builder";
+const CHANGESET: &str = "intermediate_changeset";
+const FIELD_ATTR: &str = "intermediate_field_attr";
+const ATTR: &str = "intermediate_attr";
+const ONLY: &str = "intermediate_only";
+const SPECS: &str = "intermediates";
+const INSERTABLE: &str = "intermediate_insertable";
+const INSERT: &str = "intermediate_insert";
 const DIESEL_TABLE_NAME: &str = "table_name";
+const DIESEL_PRIMARY_KEY: &str = "primary_key";
+/// The diesel derives that read `#[table_name]`, it is an unknown attribute
+/// on a struct without any of them
+const TABLE_NAME_DERIVES: &[&str] = &[
+    "Insertable",
+    "AsChangeset",
+    "Identifiable",
+    "Associations",
+    "QueryableByName",
+];
 
 #[doc(hidden)]
 #[proc_macro_derive(
     DieselIntermediate,
-    attributes(intermediate_exclude, intermediate_derive, intermediate_table_name)
+    attributes(
+        intermediate_exclude,
+        intermediate_derive,
+        intermediate_table_name,
+        intermediate_names,
+        intermediate_constructor,
+        intermediate_ref,
+        intermediate_builder,
+        intermediate_changeset,
+        intermediate_field_attr,
+        intermediate_attr,
+        intermediate_only,
+        intermediates,
+        intermediate_insertable,
+        intermediate_insert
+    )
 )]
-pub fn diesel_intermediate_fields(input: TokenStream) -> TokenStream {
-    let source = input.to_string();
+pub fn diesel_intermediate_fields(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
 
-    let ast = syn::parse_derive_input(&source).unwrap();
+    expand_diesel_intermediate_fields(&ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
 
-    let expanded = expand_diesel_intermediate_fields(&ast);
+fn expand_diesel_intermediate_fields(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let mut errors = Errors::default();
 
-    expanded.parse().unwrap()
-}
+    if let Data::Union(ref data) = ast.data {
+        return Err(syn::Error::new_spanned(
+            data.union_token,
+            "#[derive(DieselIntermediate)] can only be used with structs and enums",
+        ));
+    }
 
-fn expand_diesel_intermediate_fields(ast: &DeriveInput) -> Tokens {
-    let fields = match ast.body {
-        Body::Struct(ref data) => data.fields(),
-        Body::Enum(_) => panic!("#[derive(DieselIntermediate)] can only be used with structs"),
+    let derives = extract_derives(&ast.attrs, &mut errors);
+    let table_names = extract_table_names(&ast.attrs, &mut errors);
+    let attrs = extract_attrs(&ast.attrs, &mut errors);
+    let naming = extract_naming(&ast.attrs, &mut errors);
+    let with_ref = extract_flag(&ast.attrs, REF, &mut errors).is_some();
+    let builder = extract_flag(&ast.attrs, BUILDER, &mut errors);
+    let changeset = extract_changeset(&ast.attrs, &mut errors);
+    let specs = extract_specs(&ast.attrs, &mut errors);
+    let insertable = extract_insertable(&ast.attrs, &mut errors);
+    let insert = extract_insert(&ast.attrs, &mut errors);
+    let primary_key = extract_primary_key(&ast.attrs, &mut errors);
+    let shape = match ast.data {
+        Data::Struct(ref data) => {
+            let mut intermediates = extract_intermediates(&data.fields, &mut errors);
+            apply_specs(&specs, &mut intermediates, &mut errors);
+            Shape::Struct(intermediates)
+        }
+        Data::Enum(ref data) => {
+            for attr in ast.attrs.iter().filter(|a| a.path().is_ident(SPECS)) {
+                errors.push(attr, format!("#[{}] can only be used with structs", SPECS));
+            }
+            Shape::Enum(extract_variant_intermediates(data, &mut errors))
+        }
+        Data::Union(_) => unreachable!(),
     };
 
-    // look, you gotta do what you gotta do.
-    // I know that I don't gotta do this but it's easy and it works.
-    let derives = extract_items(&ast.attrs, DERIVE);
-    let derive_attr = format!("#[derive({})]", derives.join(","));
-    let derive_attr = syn::parse_outer_attr(&derive_attr).unwrap();
+    if let (Some(attr), Shape::Enum(_)) = (builder, &shape) {
+        errors.push(
+            attr,
+            format!("#[{}] can only be used with structs", BUILDER),
+        );
+    }
+    if let (Some((attr, _)), Shape::Enum(_)) = (insert, &shape) {
+        errors.push(attr, format!("#[{}] can only be used with structs", INSERT));
+    }
+    if let (Some(name), Shape::Enum(_)) = (&changeset, &shape) {
+        errors.push(
+            name,
+            format!("#[{}] can only be used with structs", CHANGESET),
+        );
+    }
+
+    for name in shape.only_names() {
+        let excluded_into = shape.variants().into_iter().any(|intermediates| {
+            intermediates
+                .prefix_excluded
+                .iter()
+                .any(|(prefix, _)| name == prefix)
+        });
+        if excluded_into {
+            errors.push(
+                name,
+                format!(
+                    "`{}` is used with both #[{}] and #[{}], an intermediate can only use one",
+                    name, EXCLUDE, ONLY
+                ),
+            );
+        }
+    }
+
+    if let Some(ref changeset) = changeset {
+        if changeset == "New" || shape.prefixes().iter().any(|p| changeset == p) {
+            errors.push(
+                changeset,
+                format!("`{}` is already the name of an intermediate", changeset),
+            );
+        }
+    }
+    // the changeset can be given derives, a table and a name like any other
+    // intermediate, but it has no constructors
+    let changeset_names = changeset.iter().collect::<Vec<_>>();
+    for (name, _) in &derives.keyed {
+        check_intermediate_name(name, &shape, &changeset_names, &mut errors);
+    }
+    for (name, _) in &table_names.keyed {
+        check_intermediate_name(name, &shape, &changeset_names, &mut errors);
+    }
+    for (name, _) in &attrs.keyed {
+        check_intermediate_name(name, &shape, &changeset_names, &mut errors);
+    }
+    for (name, _) in &naming.explicit {
+        check_intermediate_name(name, &shape, &changeset_names, &mut errors);
+    }
+    for (name, _) in &naming.constructors {
+        check_intermediate_name(name, &shape, &[], &mut errors);
+    }
+    for name in insertable.iter().flatten() {
+        if check_intermediate_name(name, &shape, &changeset_names, &mut errors)
+            && table_names.table_for(&name.to_string()).is_none()
+        {
+            errors.push(
+                name,
+                format!("`{}` has no table name to derive `Insertable` for", name),
+            );
+        }
+    }
+    for intermediates in shape.variants() {
+        for (index, name, _) in &intermediates.field_attrs {
+            if !check_intermediate_name(name, &shape, &changeset_names, &mut errors) {
+                continue;
+            }
+            let prefix = if changeset.as_ref() == Some(name) {
+                "New".to_string()
+            } else {
+                name.to_string()
+            };
+            if !intermediates.fields_of(&prefix).contains(index) {
+                errors.push(
+                    name,
+                    format!("this field is not in the `{}` intermediate", name),
+                );
+            }
+        }
+    }
+
+    let insertable = match insertable {
+        Some(names) => names.iter().map(|n| n.to_string()).collect(),
+        None => vec!["New".to_string()],
+    };
+    let backend = insert.map(|(attr, backend)| {
+        if !insertable
+            .iter()
+            .any(|i| table_names.table_for(i).is_some())
+        {
+            errors.push(
+                attr,
+                format!(
+                    "#[{}] needs an insertable intermediate with a table name",
+                    INSERT
+                ),
+            );
+        }
+        // MySQL can only find the row again by its primary key, which is
+        // either in the intermediate or the `AUTO_INCREMENT` column
+        if let (Backend::Mysql, Shape::Struct(ref intermediates)) = (backend, &shape) {
+            for prefix in insertable
+                .iter()
+                .filter(|p| table_names.table_for(p).is_some())
+            {
+                if primary_key.len() > 1 && intermediates.key_fields(prefix, &primary_key).is_none()
+                {
+                    errors.push(
+                        attr,
+                        format!(
+                            "the `{}` intermediate doesn't have every primary key column, \
+                             so the inserted row can't be found again on MySQL",
+                            prefix
+                        ),
+                    );
+                }
+            }
+        }
+        backend
+    });
 
-    let table_name_attr = extract_table_name_attr(&ast.attrs);
-    let intermediates = extract_intermediates(fields);
+    errors.check()?;
 
-    let base_name = ast.ident.to_string();
+    Ok(build_items(&Input {
+        vis: &ast.vis,
+        base_name: &ast.ident,
+        generics: &ast.generics,
+        shape,
+        derives,
+        table_names,
+        attrs,
+        naming,
+        with_ref,
+        with_builder: builder.is_some(),
+        changeset,
+        insertable,
+        backend,
+        primary_key,
+    }))
+}
 
-    let (impl_generics, _ty_generics, where_clause) = ast.generics.split_for_impl();
+/// Everything parsed out of the item that `DieselIntermediate` is derived on
+struct Input<'a> {
+    vis: &'a Visibility,
+    // The name of the full struct that everything else is an intermediate for
+    base_name: &'a Ident,
+    generics: &'a Generics,
+    shape: Shape<'a>,
+    derives: Derives,
+    table_names: TableNames,
+    attrs: ContainerAttrs,
+    naming: Naming,
+    /// whether to generate `New<type>Ref`
+    with_ref: bool,
+    /// whether to generate `New<type>Builder`
+    with_builder: bool,
+    /// the prefix of the `AsChangeset` intermediate, if there is one
+    changeset: Option<Ident>,
+    /// the intermediates that get `Insertable` if they have a table
+    insertable: Vec<String>,
+    /// the backend to generate `insert` for, if it is wanted
+    backend: Option<Backend>,
+    /// the primary key columns of the table, from diesel's `#[primary_key]`
+    primary_key: Vec<Ident>,
+}
 
-    build_items(
-        &ast.vis,
-        &intermediates,
-        &derive_attr,
-        &table_name_attr,
-        &base_name,
-        &impl_generics,
-        where_clause,
-    )
+impl Input<'_> {
+    /// The `#[derive(...)]` and `#[table_name]` attributes for the `New` or
+    /// `prefix` intermediate, with `Insertable` if it is an insert shape with
+    /// a table and any of `implied`
+    fn derive_attrs(&self, prefix: &str, implied: &[Path]) -> TokenStream {
+        let table_name = self.table_names.table_for(prefix);
+        let mut implied = implied.to_vec();
+        if self.insertable.iter().any(|i| i == prefix) && table_name.is_some() {
+            implied.push(parse_quote!(Insertable));
+        }
+        self.derives.attr_for_with(prefix, &implied, table_name)
+    }
 }
 
-/// Extract the table name
+/// Everything that is wrong with the input
 ///
-/// set by either `#[intermediate_table_name]` or `#[table_name]`, with
-/// intermediate... having higher priority
-fn extract_table_name_attr(attrs: &[Attribute]) -> Option<Attribute> {
-    let mut found = None;
-    for attr in attrs {
-        match attr.value {
-            MetaItem::NameValue(ref ident, ref literal) if ident == OVERRIDE_TABLE_NAME => {
-                let table_name_attr = format!(r#"#[table_name = {}]"#, quote!(#literal));
+/// Problems are collected instead of bailing out at the first one so that a
+/// struct with several mistakes gets all of them reported in a single build.
+#[derive(Default)]
+struct Errors {
+    error: Option<syn::Error>,
+}
+
+impl Errors {
+    fn push<T: ToTokens, D: std::fmt::Display>(&mut self, tokens: T, message: D) {
+        self.push_error(syn::Error::new_spanned(tokens, message));
+    }
+
+    fn push_error(&mut self, error: syn::Error) {
+        match self.error {
+            Some(ref mut existing) => existing.combine(error),
+            None => self.error = Some(error),
+        }
+    }
+
+    /// `Err` with every problem found so far, if there are any
+    fn check(&mut self) -> syn::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `#[table_name]` to put on each intermediate
+#[derive(Default)]
+struct TableNames {
+    /// set by either `#[intermediate_table_name = "..."]` or `#[table_name]`,
+    /// with intermediate... having higher priority
+    default: Option<LitStr>,
+    /// set by `#[intermediate_table_name(Name = "...", ...)]`
+    keyed: Vec<(Ident, LitStr)>,
+}
+
+impl TableNames {
+    /// The table of the `New` or `prefix` intermediate
+    fn table_for(&self, prefix: &str) -> Option<&LitStr> {
+        match self.keyed.iter().find(|&(name, _)| name == prefix) {
+            Some((_, table_name)) => Some(table_name),
+            None => self.default.as_ref(),
+        }
+    }
+}
 
-                return Some(syn::parse_outer_attr(&table_name_attr).unwrap());
+/// Extract the table names
+fn extract_table_names(attrs: &[Attribute], errors: &mut Errors) -> TableNames {
+    let mut table_names = TableNames::default();
+    let mut overridden = false;
+    for attr in attrs {
+        if attr.path().is_ident(OVERRIDE_TABLE_NAME) {
+            if let Meta::List(ref list) = attr.meta {
+                let keyed = extract_keyed_strs(list, "<table-name>", errors);
+                table_names.keyed.extend(keyed);
+                continue;
             }
-            MetaItem::NameValue(ref ident, _) if ident == DIESEL_TABLE_NAME => {
-                found = Some(attr.clone());
+            match name_value_str(attr) {
+                Some(table_name) => {
+                    table_names.default = Some(table_name.clone());
+                    overridden = true;
+                }
+                None => errors.push(
+                    attr,
+                    format!(
+                        r#"expected `#[{} = "<table-name>"]` or `#[{}(Name = "<table-name>", ...)]`"#,
+                        OVERRIDE_TABLE_NAME, OVERRIDE_TABLE_NAME
+                    ),
+                ),
             }
-            MetaItem::List(ref ident, _) if ident == OVERRIDE_TABLE_NAME => {
-                panic!(r#"expected [.. = "<table-name>"], not: {}"#, quote!(#attr));
+        } else if attr.path().is_ident(DIESEL_TABLE_NAME) && !overridden {
+            if let Some(table_name) = name_value_str(attr) {
+                table_names.default = Some(table_name.clone());
             }
-            _ => {}
         }
     }
 
-    found
+    table_names
 }
 
-fn build_items(
-    vis: &syn::Visibility,
-    intermediates: &IntermediateFields,
-    derive_attr: &Attribute,
-    table_name_attr: &Option<Attribute>,
-    // The name of the full struct that everything else is an intermediate for
-    base_name: &str,
-    impl_generics: &syn::ImplGenerics,
-    where_clause: &syn::WhereClause,
-) -> quote::Tokens {
-    let new_name = Ident::new("New".to_owned() + base_name);
-    let common_fields = &intermediates.common_fields;
+/// The `Name = "string"` pairs in a `#[attr(Name = "string", ...)]`
+///
+/// `placeholder` describes the string in error messages.
+fn extract_keyed_strs(
+    list: &MetaList,
+    placeholder: &str,
+    errors: &mut Errors,
+) -> Vec<(Ident, LitStr)> {
+    let items = match list.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)
+    {
+        Ok(items) => items,
+        Err(err) => {
+            errors.push_error(err);
+            return Vec::new();
+        }
+    };
+    let mut keyed = Vec::new();
+    for item in items {
+        match (item.path.get_ident(), expr_str(&item.value)) {
+            (Some(name), Some(value)) => keyed.push((name.clone(), value.clone())),
+            _ => errors.push(&item, format!(r#"expected `Name = "{}"`"#, placeholder)),
+        }
+    }
+    keyed
+}
+
+/// The string in `#[name = "string"]`, if that's the shape of the attribute
+fn name_value_str(attr: &Attribute) -> Option<&LitStr> {
+    match attr.meta {
+        Meta::NameValue(ref nv) => expr_str(&nv.value),
+        _ => None,
+    }
+}
+
+/// The string, if `expr` is a string literal
+fn expr_str(expr: &Expr) -> Option<&LitStr> {
+    match *expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(ref lit),
+            ..
+        }) => Some(lit),
+        _ => None,
+    }
+}
+
+fn build_items(input: &Input) -> TokenStream {
+    let Input {
+        vis,
+        base_name,
+        generics,
+        ref shape,
+        ref attrs,
+        ref naming,
+        with_ref,
+        with_builder,
+        ref changeset,
+        ..
+    } = *input;
+    let new_name = naming.struct_name("New", base_name);
 
     // accumulator for all the gnerated code
     let mut new_structs = quote!();
 
     // add the impl <type> { from_<intermediates>... }
-    let field_difs = intermediates.field_differences_full();
-    new_structs = add_from_impls(
-        &Ident::new(base_name),
-        &base_name,
-        &intermediates,
-        vis,
-        field_difs,
-        &new_structs,
-    );
+    new_structs = add_from_impls(input, base_name, None, &new_structs);
 
     // add the New<type> struct
+    let declaration = shape.declare(&new_name, "New", "New", generics, FieldTypes::AsDeclared);
+    let derive_attrs = input.derive_attrs("New", &[]);
+    let other_attrs = attrs.attrs_for("New");
     new_structs = quote! {
         #new_structs
 
-        #derive_attr
-        #table_name_attr
-        #vis struct #new_name #impl_generics #where_clause {
-            #(#common_fields),*
-        }
+        #derive_attrs
+        #other_attrs
+        #vis #declaration
     };
 
+    // add the New<type>Ref struct that borrows all of the New<type> fields
+    if with_ref {
+        new_structs = add_ref_intermediate(input, &new_name, &new_structs);
+    }
+
+    // add the New<type>Builder struct and New<type>::builder()
+    if let (true, Shape::Struct(intermediates)) = (with_builder, shape) {
+        new_structs = add_builder(input, intermediates, &new_name, &new_structs);
+    }
+
+    // add the <Prefix><type> changeset with every New<type> field optional
+    if let (Some(prefix), Shape::Struct(intermediates)) = (changeset, shape) {
+        new_structs = add_changeset(input, intermediates, prefix, &new_structs);
+    }
+
     // add the same as above but for every extra intermediate
-    for (prefix, extra_fields) in &intermediates.prefix_excluded {
-        let this_name = Ident::new(prefix.clone() + base_name);
+    for prefix in shape.prefixes() {
+        let this_name = naming.struct_name(&prefix, base_name);
+        let declaration = shape.declare(
+            &this_name,
+            &prefix,
+            &prefix,
+            generics,
+            FieldTypes::AsDeclared,
+        );
+        let derive_attrs = input.derive_attrs(&prefix, &[]);
+        let other_attrs = attrs.attrs_for(&prefix);
 
         new_structs = quote! {
             #new_structs
 
-            #derive_attr
-            #table_name_attr
-            #vis struct #this_name #impl_generics #where_clause {
-                #(#extra_fields),* ,
-                #(#common_fields),*
-            }
+            #derive_attrs
+            #other_attrs
+            #vis #declaration
         };
 
-        let field_difs = intermediates.field_differences(prefix);
+        new_structs = add_from_impls(input, &this_name, Some(&prefix), &new_structs);
+    }
 
-        new_structs = add_from_impls(
-            &this_name,
-            &base_name,
-            &intermediates,
-            vis,
-            field_difs,
-            &new_structs,
-        );
+    // add the impl <type> { to_<intermediates>... } for the full struct and
+    // every intermediate that has others inside of it
+    new_structs = add_projections(input, base_name, None, &new_structs);
+    for prefix in Some("New".to_string()).into_iter().chain(shape.prefixes()) {
+        let this_name = naming.struct_name(&prefix, base_name);
+        new_structs = add_projections(input, &this_name, Some(&prefix), &new_structs);
+    }
+
+    // add <Prefix><type>::insert for every intermediate that can be inserted
+    if let (Some(backend), Shape::Struct(_)) = (input.backend, shape) {
+        for prefix in &input.insertable {
+            if let Some(table_name) = input.table_names.table_for(prefix) {
+                new_structs = add_insert(input, prefix, table_name, backend, &new_structs);
+            }
+        }
     }
 
     new_structs
 }
 
+/// Add the `from_*` constructors for building `this_name` out of each
+/// intermediate that is missing some of its fields
+///
+/// `this_prefix` is `None` for the full struct.
 fn add_from_impls(
+    input: &Input,
     this_name: &Ident,
-    base_name: &str,
-    intermediates: &IntermediateFields,
-    vis: &Visibility,
-    field_differences: Vec<(String, Vec<&Field>, Vec<&Field>)>,
-    new_structs: &quote::Tokens,
-) -> quote::Tokens {
-    let base_snake = base_name.to_snake_case();
-    let base_field_idents = &to_struct_assignment_form(&intermediates.common_fields);
+    this_prefix: Option<&str>,
+    new_structs: &TokenStream,
+) -> TokenStream {
+    let Input {
+        vis,
+        base_name,
+        generics,
+        ref shape,
+        ref naming,
+        ..
+    } = *input;
+    let this_generics = shape.generics_for(this_prefix, generics);
+    let (impl_generics, this_ty_generics, where_clause) = this_generics.split_for_impl();
 
     let mut from_fns = quote!();
-    for (other_prefix, different_fields, same_fields) in field_differences {
-        let new_field_params: Vec<Field> = different_fields
-            .iter()
-            .cloned()
-            .map(|f| strip_vis_and_attrs(f.clone()))
-            .collect();
-        let new_field_names: Vec<Ident> = different_fields
-            .iter()
-            .flat_map(|f| f.ident.clone())
-            .collect();
-        let same_field_idents = to_struct_assignment_form_ref(&same_fields);
-        let from_ident = Ident::new(format!("{}{}", other_prefix, base_name));
-        let from_fn_ident = Ident::new(format!(
-            "from_{}_{}",
-            other_prefix.to_snake_case(),
-            base_snake,
-        ));
+    let mut from_impls = quote!();
+    for (other_prefix, different_fields) in shape.field_differences(this_prefix) {
+        let from_ident = naming.struct_name(&other_prefix, base_name);
+        let from_fn_ident = naming.constructor_name(&other_prefix, base_name);
+        let from_generics = shape.generics_for(Some(&other_prefix), generics);
+        let (_, from_ty_generics, _) = from_generics.split_for_impl();
+        // the intermediate being built from might use parameters that this
+        // one doesn't, if it has fields that are dropped
+        let extra = extra_generics(&from_generics, &this_generics);
+        let (fn_generics, _, fn_where_clause) = extra.split_for_impl();
+
+        let intermediates = match *shape {
+            Shape::Struct(ref intermediates) => intermediates,
+            Shape::Enum(ref variants) => {
+                // every variant gets its own constructor, which only takes
+                // the fields that are missing from that variant
+                for (variant, diff) in variants.iter().zip(&different_fields) {
+                    let variant_ident = &variant.ident;
+                    let fn_ident = format_ident!(
+                        "{}_{}",
+                        from_fn_ident,
+                        variant_ident.unraw().to_string().to_snake_case()
+                    );
+                    let (pattern, expr) = variant.fields.construct_from_base_arm(
+                        &quote!(#this_name::#variant_ident),
+                        this_prefix,
+                        &quote!(#from_ident::#variant_ident),
+                        &other_prefix,
+                        diff,
+                    );
+                    let params = variant.fields.params(diff);
+                    let params = params.iter().map(|&i| variant.fields.param_form(i));
+                    from_fns = quote! {
+                        #from_fns
+
+                        #[allow(unreachable_patterns)]
+                        #vis fn #fn_ident #fn_generics (
+                            #(#params,)*
+                            base: #from_ident #from_ty_generics
+                        ) -> ::std::result::Result<
+                            #this_name #this_ty_generics,
+                            #from_ident #from_ty_generics,
+                        > #fn_where_clause {
+                            match base {
+                                #pattern => ::std::result::Result::Ok(#expr),
+                                base => ::std::result::Result::Err(base),
+                            }
+                        }
+                    };
+                }
+                continue;
+            }
+        };
+
+        let different_fields = &different_fields[0];
+        let body = intermediates.construct_from_base(
+            this_name,
+            this_prefix,
+            &other_prefix,
+            different_fields,
+        );
+        let params = intermediates.params(different_fields);
+        let new_field_params = params.iter().map(|&i| intermediates.param_form(i));
 
         from_fns = quote! {
             #from_fns
 
-            #vis fn #from_fn_ident(#(#new_field_params),* , base: #from_ident) -> #this_name {
-                #this_name {
-                    #(#new_field_names),* ,
-                    #(#base_field_idents),* ,
-                    #(#same_field_idents),*
+            #vis fn #from_fn_ident #fn_generics (
+                #(#new_field_params,)*
+                base: #from_ident #from_ty_generics
+            ) -> #this_name #this_ty_generics #fn_where_clause {
+                #body
+            }
+        };
+
+        // `From<(missing fields..., base)>`, forwarding to the constructor
+        let from_impl_generics = merge_generics(&this_generics, &extra);
+        let (from_impl_generics, _, from_where_clause) = from_impl_generics.split_for_impl();
+        let names = params
+            .iter()
+            .map(|&i| intermediates.param_name(i))
+            .collect::<Vec<_>>();
+        let types = params.iter().map(|&i| &intermediates.fields[i].ty);
+        // when every missing field has a default this is just `From<base>`
+        let (source, pattern) = if names.is_empty() {
+            (quote!(#from_ident #from_ty_generics), quote!(base))
+        } else {
+            (
+                quote!((#(#types,)* #from_ident #from_ty_generics)),
+                quote!((#(#names,)* base)),
+            )
+        };
+        from_impls = quote! {
+            #from_impls
+
+            impl #from_impl_generics ::std::convert::From<#source>
+                for #this_name #this_ty_generics #from_where_clause
+            {
+                fn from(#pattern: #source) -> Self {
+                    #this_name::#from_fn_ident(#(#names,)* base)
                 }
             }
         };
@@ -343,151 +919,1724 @@ fn add_from_impls(
     quote! {
         #new_structs
 
-        impl #this_name {
+        impl #impl_generics #this_name #this_ty_generics #where_clause {
             #from_fns
         }
+
+        #from_impls
     }
 }
 
-/// Return the attrs, without any that have the `to_strip` ident
-fn strip_attr(attrs: &[Attribute], to_strip: &str) -> Vec<Attribute> {
-    attrs
-        .iter()
-        .cloned()
-        .filter(|a| match a.value {
-            MetaItem::Word(ref ident) if ident == to_strip => false,
-            MetaItem::List(ref ident, ..) if ident == to_strip => false,
-            _ => true,
-        })
-        .collect::<Vec<_>>()
-}
+/// Add the `to_*` projections from `from_name` down to every intermediate
+/// that only has fields it has, and a `From` impl for each that consumes
+/// `from_name`
+fn add_projections(
+    input: &Input,
+    from_name: &Ident,
+    from_prefix: Option<&str>,
+    new_structs: &TokenStream,
+) -> TokenStream {
+    let Input {
+        vis,
+        base_name,
+        generics,
+        ref shape,
+        ref naming,
+        ..
+    } = *input;
+    let from_generics = shape.generics_for(from_prefix, generics);
+    let (impl_generics, ty_generics, where_clause) = from_generics.split_for_impl();
 
-fn strip_vis_and_attrs(field: Field) -> Field {
-    Field {
-        ident: field.ident,
-        vis: syn::Visibility::Inherited,
-        attrs: vec![],
-        ty: field.ty,
-    }
-}
+    let mut to_fns = quote!();
+    let mut from_impls = quote!();
+    let prefixes = Some("New".to_string()).into_iter().chain(shape.prefixes());
+    for prefix in prefixes.filter(|p| shape.projects_to(from_prefix, p)) {
+        let this_name = naming.struct_name(&prefix, base_name);
+        let this_generics = shape.generics_for(Some(&prefix), generics);
+        let (_, this_ty_generics, _) = this_generics.split_for_impl();
+        let to_fn_ident = format_ident!("to_{}", naming.snake_name(&prefix, base_name));
 
-fn to_struct_assignment_form(fields: &[Field]) -> Vec<Tokens> {
-    fields
-        .iter()
-        .map(|f| {
-            let ident = &f.ident;
-            quote! { #ident: base.#ident }
-        })
-        .collect()
-}
+        let clone_bounds = clone_bounds(shape, &prefix);
+        let cloned = shape.project(
+            from_name,
+            from_prefix,
+            &this_name,
+            &prefix,
+            &quote!(*self),
+            Projection::Cloned,
+        );
+        let moved = shape.project(
+            from_name,
+            from_prefix,
+            &this_name,
+            &prefix,
+            &quote!(base),
+            Projection::Moved,
+        );
 
-fn to_struct_assignment_form_ref(fields: &[&Field]) -> Vec<Tokens> {
-    fields
-        .iter()
-        .map(|f| {
-            let ident = &f.ident;
-            quote! { #ident: base.#ident }
-        })
-        .collect()
-}
+        to_fns = quote! {
+            #to_fns
 
-fn extract_items(attrs: &[Attribute], attr: &str) -> Vec<String> {
-    attrs
-        .iter()
-        .filter_map(|a| match a.value {
-            MetaItem::List(ref ident, ref vals) if ident == attr => Some(vals),
-            _ => None,
-        })
-        .flat_map(|list_items| {
-            list_items.into_iter().map(|item| {
-                if let NestedMetaItem::MetaItem(MetaItem::Word(ref val)) = *item {
-                    val.to_string()
-                } else {
-                    panic!("Unexpected format for item: {} ", quote!(#item));
+            #vis fn #to_fn_ident(&self) -> #this_name #this_ty_generics
+            where
+                #(#clone_bounds,)*
+            {
+                #cloned
+            }
+        };
+        from_impls = quote! {
+            #from_impls
+
+            impl #impl_generics ::std::convert::From<#from_name #ty_generics>
+                for #this_name #this_ty_generics #where_clause
+            {
+                fn from(base: #from_name #ty_generics) -> Self {
+                    #moved
                 }
-            })
-        })
-        .collect::<Vec<_>>()
-}
+            }
+        };
+    }
 
-#[cfg_attr(feature = "cargo-clippy", allow(large_enum_variant))]
-enum ExcludeAttr<'a> {
-    /// A field that is excluded from the `New` item
-    Excluded(Field),
-    /// A field that is excluded from a named item
-    Intermediate(&'a str, Field),
-    Included,
+    if to_fns.is_empty() {
+        return new_structs.clone();
+    }
+    quote! {
+        #new_structs
+
+        impl #impl_generics #from_name #ty_generics #where_clause {
+            #to_fns
+        }
+
+        #from_impls
+    }
 }
 
-/// Parse the attributes on fields to get a list fields that should be excluded
-fn extract_intermediates(fields: &[Field]) -> IntermediateFields {
-    let mut intermediates = IntermediateFields::default();
-    // Collect the fields that aren't decorated with "exclude"
-    let common_fields = fields
-        .iter()
-        .filter(|f| {
-            use ExcludeAttr::*;
-            // If any of this fields attrs are "exclude" then we want to strip the entire field
-            match field_status(f) {
-                Excluded(field) => {
-                    intermediates.excluded_at_least_once.push(field);
-                    false
+/// Add the `New<type>Ref` intermediate, which borrows every field of
+/// `New<type>`, and the ways of building it out of a `&<type>` or a
+/// `&New<type>`
+fn add_ref_intermediate(input: &Input, new_name: &Ident, new_structs: &TokenStream) -> TokenStream {
+    let Input {
+        vis,
+        base_name,
+        generics,
+        ref shape,
+        ref attrs,
+        ref naming,
+        ..
+    } = *input;
+    let ref_name = format_ident!("{}Ref", new_name);
+    let lifetime = fresh_lifetime(generics);
+    let declaration = shape.declare(
+        &ref_name,
+        "New",
+        "New",
+        generics,
+        FieldTypes::Borrowed(&lifetime),
+    );
+    let derive_attrs = input.derive_attrs("New", &[]);
+    let other_attrs = attrs.attrs_for("New");
+    let to_fn_ident = format_ident!("to_{}_ref", naming.snake_name("New", base_name));
+
+    let mut ref_generics = shape.generics_for(Some("New"), generics);
+    ref_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+    );
+    let (_, ref_ty_generics, _) = ref_generics.split_for_impl();
+
+    let mut conversions = quote!();
+    for (from_name, from_prefix) in [(base_name, None), (new_name, Some("New"))] {
+        let from_generics = shape.generics_for(from_prefix, generics);
+        let (impl_generics, from_ty_generics, where_clause) = from_generics.split_for_impl();
+        let mut with_lifetime = from_generics.clone();
+        with_lifetime.params.insert(
+            0,
+            GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+        );
+        let (impl_generics_with_lifetime, _, _) = with_lifetime.split_for_impl();
+        let borrowed = shape.project(
+            from_name,
+            from_prefix,
+            &ref_name,
+            "New",
+            &quote!(*base),
+            Projection::Borrowed,
+        );
+
+        conversions = quote! {
+            #conversions
+
+            impl #impl_generics #from_name #from_ty_generics #where_clause {
+                #vis fn #to_fn_ident<#lifetime>(&#lifetime self) -> #ref_name #ref_ty_generics {
+                    ::std::convert::From::from(self)
                 }
-                Intermediate(intermediate_prefix, field) => {
-                    intermediates.excluded_at_least_once.push(field.clone());
-                    intermediates
-                        .prefix_excluded
-                        .entry(intermediate_prefix.to_string())
-                        .or_insert_with(Vec::new)
-                        .push(field);
-                    false
+            }
+
+            impl #impl_generics_with_lifetime ::std::convert::From<&#lifetime #from_name #from_ty_generics>
+                for #ref_name #ref_ty_generics #where_clause
+            {
+                fn from(base: &#lifetime #from_name #from_ty_generics) -> Self {
+                    #borrowed
                 }
-                Included => true,
             }
-        })
-        .cloned()
-        .collect::<Vec<_>>();
-    intermediates.common_fields = common_fields;
-    intermediates
+        };
+    }
+
+    quote! {
+        #new_structs
+
+        #derive_attrs
+        #other_attrs
+        #vis #declaration
+
+        #conversions
+    }
 }
 
-/// A list of all the fields on an original struct, grouped by their status
-#[derive(Default)]
-struct IntermediateFields {
-    /// The fields that never have an `#[intermediate_exclude]1 field on them
-    common_fields: Vec<Field>,
-    /// Every exclude annotation (either `#[intermediate_exclude]` or
-    /// `#[intermediate_exclude(Prefix)]`) will add to this list
-    excluded_at_least_once: Vec<Field>,
-    /// Fields that are excluded with a prefix are grouped by prefix here
-    prefix_excluded: HashMap<String, Vec<Field>>,
+/// Add `insert`, which inserts the `prefix` intermediate into `table_name`
+/// and returns the full struct that was inserted
+fn add_insert(
+    input: &Input,
+    prefix: &str,
+    table_name: &LitStr,
+    backend: Backend,
+    new_structs: &TokenStream,
+) -> TokenStream {
+    let Input {
+        vis,
+        base_name,
+        generics,
+        ref shape,
+        ref naming,
+        ..
+    } = *input;
+    let this_name = naming.struct_name(prefix, base_name);
+    let this_generics = shape.generics_for(Some(prefix), generics);
+    let (impl_generics, this_ty_generics, where_clause) = this_generics.split_for_impl();
+    let (_, base_ty_generics, _) = generics.split_for_impl();
+    let extra = extra_generics(generics, &this_generics);
+    let (fn_generics, _, _) = extra.split_for_impl();
+    let extra_predicates = extra.where_clause.iter().flat_map(|w| &w.predicates);
+    let table = match table_name.parse::<Path>() {
+        Ok(table) => table,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    // without `RETURNING` the row has to be found again, by the primary key
+    // if the intermediate has it and by the one the database just picked if
+    // it doesn't
+    let key_filter = match shape {
+        Shape::Struct(intermediates) => intermediates.key_fields(prefix, &input.primary_key),
+        Shape::Enum(_) => None,
+    };
+    let inserted = match (key_filter, backend) {
+        (Some(fields), _) => fields.iter().fold(quote!(#table::table), |query, column| {
+            quote! {
+                ::diesel::QueryDsl::filter(
+                    #query,
+                    ::diesel::ExpressionMethods::eq(#table::#column, &self.#column),
+                )
+            }
+        }),
+        (None, Backend::Sqlite) => quote! {
+            ::diesel::QueryDsl::filter(
+                #table::table,
+                ::diesel::dsl::sql::<::diesel::sql_types::Bool>("rowid = last_insert_rowid()"),
+            )
+        },
+        (None, _) => quote! {
+            ::diesel::QueryDsl::filter(
+                #table::table,
+                ::diesel::ExpressionMethods::eq(
+                    ::diesel::Table::primary_key(&#table::table),
+                    ::diesel::dsl::sql::<
+                        <<#table::table as ::diesel::Table>::PrimaryKey
+                            as ::diesel::Expression>::SqlType,
+                    >("LAST_INSERT_ID()"),
+                ),
+            )
+        },
+    };
+    let body = match backend {
+        Backend::Pg => quote! {
+            ::diesel::RunQueryDsl::get_result(
+                ::diesel::insert_into(#table::table).values(&self),
+                conn,
+            )
+        },
+        Backend::Sqlite | Backend::Mysql => quote! {
+            ::diesel::Connection::transaction(conn, || {
+                ::diesel::RunQueryDsl::execute(
+                    ::diesel::insert_into(#table::table).values(&self),
+                    conn,
+                )?;
+                ::diesel::RunQueryDsl::first(#inserted, conn)
+            })
+        },
+    };
+    let connection = backend.connection();
+    // higher-ranked like the `Clone` bounds, so that a generic struct only
+    // has to be `Queryable` where `insert` is called
+    let queryable_bound = quote! {
+        for<'__insert> #base_name #base_ty_generics: ::diesel::Queryable<
+            <#table::table as ::diesel::query_builder::AsQuery>::SqlType,
+            <#connection as ::diesel::Connection>::Backend,
+        >
+    };
+
+    quote! {
+        #new_structs
+
+        impl #impl_generics #this_name #this_ty_generics #where_clause {
+            /// Insert this into the table and get back everything that the
+            /// database filled in
+            #vis fn insert #fn_generics (
+                self,
+                conn: &#connection,
+            ) -> ::diesel::QueryResult<#base_name #base_ty_generics>
+            where
+                #(#extra_predicates,)*
+                #queryable_bound
+            {
+                #body
+            }
+        }
+    }
 }
 
-impl IntermediateFields {
-    /// All groups of items that are field subsets of the current prefix
-    ///
-    /// So given a struct like:
-    ///
-    /// ```rust,ignore
-    /// #[derive(DieselIntermediate)]
-    /// struct Big {
-    ///     #[intermediate_exclude],
-    ///     id: i32,
-    ///     #[intermediate_exclude],
+/// The diesel backend that `#[intermediate_insert]` generates code for
+#[derive(Clone, Copy)]
+enum Backend {
+    Pg,
+    Sqlite,
+    Mysql,
+}
+
+impl Backend {
+    const ALL: [Backend; 3] = [Backend::Pg, Backend::Sqlite, Backend::Mysql];
+
+    /// The name of the backend in `#[intermediate_insert(backend = "...")]`,
+    /// which is the name of its module in diesel
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Pg => "pg",
+            Backend::Sqlite => "sqlite",
+            Backend::Mysql => "mysql",
+        }
+    }
+
+    fn connection(self) -> TokenStream {
+        match self {
+            Backend::Pg => quote!(::diesel::pg::PgConnection),
+            Backend::Sqlite => quote!(::diesel::sqlite::SqliteConnection),
+            Backend::Mysql => quote!(::diesel::mysql::MysqlConnection),
+        }
+    }
+}
+
+/// Add `New<type>Builder`, which collects the fields of `New<type>` one at a
+/// time and reports the first one that is missing when it is built
+fn add_builder(
+    input: &Input,
+    intermediates: &IntermediateFields,
+    new_name: &Ident,
+    new_structs: &TokenStream,
+) -> TokenStream {
+    let Input {
+        vis,
+        generics,
+        ref shape,
+        ..
+    } = *input;
+    let builder_name = format_ident!("{}Builder", new_name);
+    let new_generics = shape.generics_for(Some("New"), generics);
+    let (impl_generics, ty_generics, where_clause) = new_generics.split_for_impl();
+
+    let fields = intermediates.fields_of("New");
+    let names = fields
+        .iter()
+        .map(|&i| intermediates.param_name(i))
+        .collect::<Vec<_>>();
+    let types = fields
+        .iter()
+        .map(|&i| &intermediates.fields[i].ty)
+        .collect::<Vec<_>>();
+    let members = fields.iter().map(|&i| intermediates.member(i, &fields));
+    let missing = names
+        .iter()
+        .map(|name| format!("`{}` was not set on `{}`", name.unraw(), builder_name));
+
+    quote! {
+        #new_structs
+
+        #vis struct #builder_name #new_generics #where_clause {
+            #(#names: ::std::option::Option<#types>,)*
+        }
+
+        impl #impl_generics ::std::default::Default for #builder_name #ty_generics #where_clause {
+            fn default() -> Self {
+                #builder_name {
+                    #(#names: ::std::option::Option::None,)*
+                }
+            }
+        }
+
+        impl #impl_generics #builder_name #ty_generics #where_clause {
+            #(
+                #vis fn #names(mut self, #names: #types) -> Self {
+                    self.#names = ::std::option::Option::Some(#names);
+                    self
+                }
+            )*
+
+            /// The built intermediate, or the name of the first field that
+            /// was never set
+            #vis fn build(self) -> ::std::result::Result<#new_name #ty_generics, &'static str> {
+                ::std::result::Result::Ok(#new_name {
+                    #(#members: match self.#names {
+                        ::std::option::Option::Some(value) => value,
+                        ::std::option::Option::None => {
+                            return ::std::result::Result::Err(#missing)
+                        }
+                    },)*
+                })
+            }
+        }
+
+        impl #impl_generics #new_name #ty_generics #where_clause {
+            #vis fn builder() -> #builder_name #ty_generics {
+                ::std::default::Default::default()
+            }
+        }
+    }
+}
+
+/// Add the `AsChangeset` intermediate, which has every field of `New<type>`
+/// as an `Option`, and its `apply` for updating a `<type>` in place
+fn add_changeset(
+    input: &Input,
+    intermediates: &IntermediateFields,
+    prefix: &Ident,
+    new_structs: &TokenStream,
+) -> TokenStream {
+    let Input {
+        vis,
+        base_name,
+        generics,
+        ref shape,
+        ref attrs,
+        ref naming,
+        ..
+    } = *input;
+    let prefix = prefix.to_string();
+    let this_name = naming.struct_name(&prefix, base_name);
+    let declaration = shape.declare(&this_name, "New", &prefix, generics, FieldTypes::Optional);
+    let derive_attrs = input.derive_attrs(&prefix, &[parse_quote!(AsChangeset)]);
+    let other_attrs = attrs.attrs_for(&prefix);
+
+    let this_generics = shape.generics_for(Some("New"), generics);
+    let (impl_generics, this_ty_generics, where_clause) = this_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    // the parameters that only the full struct has go on `apply` itself
+    let extra = extra_generics(generics, &this_generics);
+    let (fn_generics, _, _) = extra.split_for_impl();
+    let extra_predicates = extra.where_clause.iter().flat_map(|w| &w.predicates);
+
+    let fields = intermediates.fields_of("New");
+    let this_members = fields
+        .iter()
+        .map(|&i| intermediates.member(i, &fields))
+        .collect::<Vec<_>>();
+    let full_fields = intermediates.full_fields();
+    let full_members = fields
+        .iter()
+        .map(|&i| intermediates.member(i, &full_fields));
+    let clone_bounds = clone_bounds(shape, "New");
+
+    quote! {
+        #new_structs
+
+        #derive_attrs
+        #other_attrs
+        #vis #declaration
+
+        impl #impl_generics #this_name #this_ty_generics #where_clause {
+            /// Overwrite the fields of `target` that are set in this changeset
+            #vis fn apply #fn_generics (&self, target: &mut #base_name #ty_generics)
+            where
+                #(#extra_predicates,)*
+                #(#clone_bounds,)*
+            {
+                #(
+                    if let ::std::option::Option::Some(ref value) = self.#this_members {
+                        target.#full_members = ::std::clone::Clone::clone(value);
+                    }
+                )*
+            }
+        }
+    }
+}
+
+/// A lifetime that none of `generics` is called, `'a` if possible
+fn fresh_lifetime(generics: &Generics) -> Lifetime {
+    let taken = generics
+        .params
+        .iter()
+        .map(generic_param_name)
+        .collect::<HashSet<_>>();
+    let mut name = "'a".to_string();
+    while taken.contains(&name) {
+        name.push('_');
+    }
+    Lifetime::new(&name, proc_macro2::Span::call_site())
+}
+
+/// The types that `Shape::declare` gives the fields
+#[derive(Clone, Copy)]
+enum FieldTypes<'a> {
+    AsDeclared,
+    /// a reference with this lifetime to the declared type
+    Borrowed(&'a Lifetime),
+    /// `Option` of the declared type
+    Optional,
+}
+
+/// How `Shape::project` gets the fields out of the item it projects from
+#[derive(Clone, Copy)]
+enum Projection {
+    Moved,
+    Cloned,
+    Borrowed,
+}
+
+/// The fields of the input, grouped by which intermediates they belong to
+enum Shape<'a> {
+    Struct(IntermediateFields),
+    /// Every variant gets its fields grouped independently, but all of them
+    /// share the same set of intermediates
+    Enum(Vec<VariantFields<'a>>),
+}
+
+struct VariantFields<'a> {
+    ident: &'a Ident,
+    attrs: Vec<&'a Attribute>,
+    discriminant: Option<&'a Expr>,
+    fields: IntermediateFields,
+}
+
+impl Shape<'_> {
+    fn variants(&self) -> Vec<&IntermediateFields> {
+        match *self {
+            Shape::Struct(ref intermediates) => vec![intermediates],
+            Shape::Enum(ref variants) => variants.iter().map(|v| &v.fields).collect(),
+        }
+    }
+
+    /// The names of every prefixed intermediate
+    fn prefixes(&self) -> Vec<String> {
+        let mut prefixes: Vec<String> = Vec::new();
+        for intermediates in self.variants() {
+            for (prefix, _) in &intermediates.prefix_excluded {
+                if !prefixes.contains(prefix) {
+                    prefixes.push(prefix.clone());
+                }
+            }
+        }
+        for name in self.only_names() {
+            let name = name.to_string();
+            if !prefixes.contains(&name) {
+                prefixes.push(name);
+            }
+        }
+        prefixes
+    }
+
+    /// The names of the intermediates from `#[intermediate_only]`, as they
+    /// were first written
+    fn only_names(&self) -> Vec<&Ident> {
+        let mut names: Vec<&Ident> = Vec::new();
+        for intermediates in self.variants() {
+            for (name, _) in &intermediates.included_only {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// The `struct` or `enum` item for the `New` or a prefixed intermediate
+    ///
+    /// `attrs_for` picks the `#[intermediate_field_attr]`s that the fields
+    /// get, it's only different from `prefix` for the changeset.
+    fn declare(
+        &self,
+        name: &Ident,
+        prefix: &str,
+        attrs_for: &str,
+        generics: &Generics,
+        field_types: FieldTypes,
+    ) -> TokenStream {
+        let mut generics = self.generics_for(Some(prefix), generics);
+        if let FieldTypes::Borrowed(lifetime) = field_types {
+            generics.params.insert(
+                0,
+                GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+            );
+        }
+        let where_clause = &generics.where_clause;
+        match *self {
+            Shape::Struct(ref intermediates) => {
+                let fields = intermediates.fields_of(prefix);
+                let body = intermediates.fields_body(&fields, field_types, attrs_for);
+                match intermediates.style {
+                    FieldsStyle::Named => quote! { struct #name #generics #where_clause #body },
+                    FieldsStyle::Tuple | FieldsStyle::Unit => {
+                        quote! { struct #name #generics #body #where_clause ; }
+                    }
+                }
+            }
+            Shape::Enum(ref variants) => {
+                let variants = variants.iter().map(|variant| {
+                    let attrs = &variant.attrs;
+                    let ident = variant.ident;
+                    let body = variant.fields.fields_body(
+                        &variant.fields.fields_of(prefix),
+                        field_types,
+                        attrs_for,
+                    );
+                    let discriminant = variant.discriminant.map(|d| quote!(= #d));
+                    quote! { #(#attrs)* #ident #body #discriminant }
+                });
+                quote! { enum #name #generics #where_clause { #(#variants),* } }
+            }
+        }
+    }
+
+    /// The parameters of `generics` that are used by the full struct or the
+    /// `prefix` intermediate
+    fn generics_for(&self, prefix: Option<&str>, generics: &Generics) -> Generics {
+        match prefix {
+            Some(prefix) => used_generics(
+                generics,
+                self.variants().into_iter().flat_map(|intermediates| {
+                    intermediates
+                        .fields_of(prefix)
+                        .into_iter()
+                        .map(move |i| &intermediates.fields[i].ty)
+                }),
+            ),
+            None => generics.clone(),
+        }
+    }
+
+    /// The types of the fields of the `prefix` intermediate, in every variant
+    fn types_of(&self, prefix: &str) -> Vec<&Type> {
+        self.variants()
+            .into_iter()
+            .flat_map(|intermediates| {
+                intermediates
+                    .fields_of(prefix)
+                    .into_iter()
+                    .map(move |i| &intermediates.fields[i].ty)
+            })
+            .collect()
+    }
+
+    /// Whether every field of the `this_prefix` intermediate is also in the
+    /// full struct or the `from_prefix` intermediate, in every variant
+    fn projects_to(&self, from_prefix: Option<&str>, this_prefix: &str) -> bool {
+        from_prefix != Some(this_prefix)
+            && self.variants().into_iter().all(|intermediates| {
+                let from_fields = intermediates.fields_for(from_prefix);
+                intermediates
+                    .fields_of(this_prefix)
+                    .iter()
+                    .all(|i| from_fields.contains(i))
+            })
+    }
+
+    /// Build `this_name` out of the fields that it shares with `from_name`,
+    /// taking them out of `scrutinee` as `projection` says
+    fn project(
+        &self,
+        from_name: &Ident,
+        from_prefix: Option<&str>,
+        this_name: &Ident,
+        this_prefix: &str,
+        scrutinee: &TokenStream,
+        projection: Projection,
+    ) -> TokenStream {
+        let arms = match *self {
+            Shape::Struct(ref intermediates) => vec![intermediates.project_arm(
+                &quote!(#from_name),
+                from_prefix,
+                &quote!(#this_name),
+                this_prefix,
+                projection,
+            )],
+            Shape::Enum(ref variants) => variants
+                .iter()
+                .map(|variant| {
+                    let variant_ident = &variant.ident;
+                    variant.fields.project_arm(
+                        &quote!(#from_name::#variant_ident),
+                        from_prefix,
+                        &quote!(#this_name::#variant_ident),
+                        this_prefix,
+                        projection,
+                    )
+                })
+                .collect(),
+        };
+        let arms = arms
+            .into_iter()
+            .map(|(pattern, expr)| quote! { #pattern => #expr });
+        quote! {
+            match #scrutinee {
+                #(#arms,)*
+            }
+        }
+    }
+
+    /// For every intermediate that `this_prefix` can be built from, the
+    /// fields that are missing from it in each variant
+    fn field_differences(&self, this_prefix: Option<&str>) -> Vec<(String, Vec<Vec<usize>>)> {
+        let variants = self.variants();
+        let mut differences: Vec<(String, Vec<Vec<usize>>)> = Vec::new();
+        for (v, intermediates) in variants.iter().enumerate() {
+            let variant_differences = match this_prefix {
+                Some(prefix) => intermediates.field_differences(prefix),
+                None => intermediates.field_differences_full(),
+            };
+            for (other_prefix, different_fields, _same_fields) in variant_differences {
+                let position = match differences.iter().position(|d| d.0 == other_prefix) {
+                    Some(position) => position,
+                    None => {
+                        differences.push((other_prefix, vec![Vec::new(); variants.len()]));
+                        differences.len() - 1
+                    }
+                };
+                differences[position].1[v] = different_fields;
+            }
+        }
+        differences
+    }
+}
+
+/// A `Clone` bound for every distinct field type of the `prefix`
+/// intermediate
+///
+/// The bounds are higher-ranked so that they aren't checked until the
+/// function is called, a field that isn't `Clone` only makes the function
+/// unusable instead of breaking the derive.
+fn clone_bounds(shape: &Shape, prefix: &str) -> Vec<TokenStream> {
+    let mut bounds = Vec::new();
+    for ty in shape.types_of(prefix) {
+        let bound = quote!(for<'__clone> #ty: ::std::clone::Clone);
+        if !bounds
+            .iter()
+            .any(|b: &TokenStream| b.to_string() == bound.to_string())
+        {
+            bounds.push(bound);
+        }
+    }
+    bounds
+}
+
+/// `generics` without the parameters that none of `types` use, so that
+/// intermediates don't end up with unused parameters
+///
+/// Bounds and predicates that mention a dropped parameter are dropped too,
+/// a struct declaration doesn't need them.
+fn used_generics<'a>(generics: &Generics, types: impl IntoIterator<Item = &'a Type>) -> Generics {
+    let mut used = HashSet::new();
+    for ty in types {
+        collect_names(ty.to_token_stream(), &mut used);
+    }
+
+    let unused = generics
+        .params
+        .iter()
+        .map(generic_param_name)
+        .filter(|name| !used.contains(name))
+        .collect::<HashSet<_>>();
+    let mentions_unused = |tokens: TokenStream| {
+        let mut names = HashSet::new();
+        collect_names(tokens, &mut names);
+        !names.is_disjoint(&unused)
+    };
+    let predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|w| &w.predicates)
+        .filter(|p| !mentions_unused(p.to_token_stream()))
+        .cloned()
+        .collect::<Punctuated<_, Token![,]>>();
+    Generics {
+        params: generics
+            .params
+            .iter()
+            .filter(|p| !unused.contains(&generic_param_name(p)))
+            .map(|p| {
+                let mut p = p.clone();
+                match p {
+                    GenericParam::Type(ref mut t) => {
+                        t.bounds = t
+                            .bounds
+                            .iter()
+                            .filter(|b| !mentions_unused(b.to_token_stream()))
+                            .cloned()
+                            .collect();
+                        if t.bounds.is_empty() {
+                            t.colon_token = None;
+                        }
+                    }
+                    GenericParam::Lifetime(ref mut l) => {
+                        l.bounds = l
+                            .bounds
+                            .iter()
+                            .filter(|b| !unused.contains(&b.to_string()))
+                            .cloned()
+                            .collect();
+                        if l.bounds.is_empty() {
+                            l.colon_token = None;
+                        }
+                    }
+                    GenericParam::Const(_) => {}
+                }
+                p
+            })
+            .collect(),
+        where_clause: generics.where_clause.as_ref().and_then(|w| {
+            if predicates.is_empty() {
+                None
+            } else {
+                Some(WhereClause {
+                    where_token: w.where_token,
+                    predicates,
+                })
+            }
+        }),
+        ..generics.clone()
+    }
+}
+
+/// The parameters and predicates of both, with lifetimes first as Rust
+/// requires
+fn merge_generics(a: &Generics, b: &Generics) -> Generics {
+    let (lifetimes, others): (Vec<_>, Vec<_>) = a
+        .params
+        .iter()
+        .chain(&b.params)
+        .cloned()
+        .partition(|p| matches!(p, GenericParam::Lifetime(_)));
+    let predicates = a
+        .where_clause
+        .iter()
+        .chain(&b.where_clause)
+        .flat_map(|w| w.predicates.iter().cloned())
+        .collect::<Punctuated<_, Token![,]>>();
+    Generics {
+        params: lifetimes.into_iter().chain(others).collect(),
+        where_clause: if predicates.is_empty() {
+            None
+        } else {
+            Some(WhereClause {
+                where_token: Default::default(),
+                predicates,
+            })
+        },
+        ..a.clone()
+    }
+}
+
+/// The parameters and predicates of `generics` that aren't in `existing`
+///
+/// Bounds that `existing` dropped from a parameter they share come back as
+/// predicates, so that types using `generics` are well-formed.
+fn extra_generics(generics: &Generics, existing: &Generics) -> Generics {
+    let existing_params = existing
+        .params
+        .iter()
+        .map(generic_param_name)
+        .collect::<HashSet<_>>();
+    let existing_predicates = existing
+        .where_clause
+        .iter()
+        .flat_map(|w| &w.predicates)
+        .collect::<Vec<_>>();
+    let mut predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|w| &w.predicates)
+        .filter(|p| !existing_predicates.contains(p))
+        .cloned()
+        .collect::<Punctuated<_, Token![,]>>();
+    for param in &generics.params {
+        let existing_param = existing
+            .params
+            .iter()
+            .find(|e| generic_param_name(e) == generic_param_name(param));
+        match (param, existing_param) {
+            (GenericParam::Type(t), Some(GenericParam::Type(e))) => {
+                let ident = &t.ident;
+                let dropped = t
+                    .bounds
+                    .iter()
+                    .filter(|b| !e.bounds.iter().any(|eb| eb == *b));
+                let dropped = dropped.collect::<Vec<_>>();
+                if !dropped.is_empty() {
+                    predicates.push(parse_quote!(#ident: #(#dropped)+*));
+                }
+            }
+            (GenericParam::Lifetime(l), Some(GenericParam::Lifetime(e))) => {
+                let lifetime = &l.lifetime;
+                let dropped = l
+                    .bounds
+                    .iter()
+                    .filter(|b| !e.bounds.iter().any(|eb| eb == *b));
+                let dropped = dropped.collect::<Vec<_>>();
+                if !dropped.is_empty() {
+                    predicates.push(parse_quote!(#lifetime: #(#dropped)+*));
+                }
+            }
+            _ => {}
+        }
+    }
+    Generics {
+        params: generics
+            .params
+            .iter()
+            .filter(|p| !existing_params.contains(&generic_param_name(p)))
+            .cloned()
+            .collect(),
+        where_clause: if predicates.is_empty() {
+            None
+        } else {
+            Some(WhereClause {
+                where_token: Default::default(),
+                predicates,
+            })
+        },
+        ..generics.clone()
+    }
+}
+
+/// How a generic parameter is referred to: `T`, `N` or `'a`
+fn generic_param_name(param: &GenericParam) -> String {
+    match *param {
+        GenericParam::Type(ref t) => t.ident.to_string(),
+        GenericParam::Lifetime(ref l) => l.lifetime.to_string(),
+        GenericParam::Const(ref c) => c.ident.to_string(),
+    }
+}
+
+/// Every identifier and lifetime in `tokens`, as `T` or `'a`
+fn collect_names(tokens: TokenStream, names: &mut HashSet<String>) {
+    let mut after_apostrophe = false;
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => collect_names(group.stream(), names),
+            TokenTree::Ident(ident) if after_apostrophe => {
+                names.insert(format!("'{}", ident));
+            }
+            TokenTree::Ident(ident) => {
+                names.insert(ident.to_string());
+            }
+            TokenTree::Punct(ref punct) if punct.as_char() == '\'' => {
+                after_apostrophe = true;
+                continue;
+            }
+            TokenTree::Punct(_) | TokenTree::Literal(_) => {}
+        }
+        after_apostrophe = false;
+    }
+}
+
+/// The contents of `#[intermediate_field_attr(Name, attr)]`
+struct FieldAttr {
+    name: Ident,
+    meta: Meta,
+}
+
+impl Parse for FieldAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let meta = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(FieldAttr { name, meta })
+    }
+}
+
+/// Return the attrs, without any that have the `to_strip` ident
+fn strip_attr(attrs: &[Attribute], to_strip: &str) -> Vec<Attribute> {
+    attrs
+        .iter()
+        .filter(|a| !a.path().is_ident(to_strip))
+        .cloned()
+        .collect::<Vec<_>>()
+}
+
+/// How the generated intermediates are named
+///
+/// By default the name of the intermediate is put in front of the name of the
+/// struct (`NewRust`, `CapturedRust`). `#[intermediate_names(suffix)]` puts it
+/// after (`RustNew`, `RustCaptured`), and
+/// `#[intermediate_names(New = "RustInsert")]` names one intermediate
+/// explicitly.
+///
+/// The `from_*` constructors are named after the intermediate that they
+/// build from, unless `#[intermediate_constructor(New = "persisted")]` names
+/// them explicitly.
+#[derive(Default)]
+struct Naming {
+    suffix: bool,
+    explicit: Vec<(Ident, Ident)>,
+    constructors: Vec<(Ident, Ident)>,
+}
+
+impl Naming {
+    /// The name of the `New` or `prefix` intermediate of `base_name`
+    fn struct_name(&self, prefix: &str, base_name: &Ident) -> Ident {
+        match self.explicit.iter().find(|&(name, _)| name == prefix) {
+            Some((_, explicit)) => explicit.clone(),
+            None if self.suffix => format_ident!("{}{}", base_name, prefix),
+            None => format_ident!("{}{}", prefix, base_name),
+        }
+    }
+
+    /// `struct_name` in snake case, for use in function names
+    fn snake_name(&self, prefix: &str, base_name: &Ident) -> String {
+        let base_snake = base_name.unraw().to_string().to_snake_case();
+        match self.explicit.iter().find(|&(name, _)| name == prefix) {
+            Some((_, explicit)) => explicit.unraw().to_string().to_snake_case(),
+            None if self.suffix => format!("{}_{}", base_snake, prefix.to_snake_case()),
+            None => format!("{}_{}", prefix.to_snake_case(), base_snake),
+        }
+    }
+
+    /// The name of the constructors that take the `New` or `prefix`
+    /// intermediate of `base_name`
+    fn constructor_name(&self, prefix: &str, base_name: &Ident) -> Ident {
+        match self.constructors.iter().find(|&(name, _)| name == prefix) {
+            Some((_, explicit)) => explicit.clone(),
+            None => format_ident!("from_{}", self.snake_name(prefix, base_name)),
+        }
+    }
+}
+
+/// The `#[name]` attribute with no arguments, if it is present
+fn extract_flag<'a>(
+    attrs: &'a [Attribute],
+    name: &str,
+    errors: &mut Errors,
+) -> Option<&'a Attribute> {
+    let mut present = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident(name)) {
+        match attr.meta {
+            Meta::Path(_) => present = Some(attr),
+            _ => errors.push(attr, format!("expected `#[{}]`", name)),
+        }
+    }
+    present
+}
+
+/// The backend in `#[intermediate_insert(backend = "...")]`, if it is
+/// present
+fn extract_insert<'a>(
+    attrs: &'a [Attribute],
+    errors: &mut Errors,
+) -> Option<(&'a Attribute, Backend)> {
+    let mut insert = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident(INSERT)) {
+        let backend = attr
+            .parse_args::<MetaNameValue>()
+            .ok()
+            .filter(|nv| nv.path.is_ident("backend"))
+            .and_then(|nv| {
+                let name = expr_str(&nv.value)?.value();
+                Backend::ALL.into_iter().find(|b| b.name() == name)
+            });
+        match backend {
+            Some(backend) => insert = Some((attr, backend)),
+            None => errors.push(
+                attr,
+                format!(
+                    r#"expected `#[{}(backend = "pg" | "sqlite" | "mysql")]`"#,
+                    INSERT
+                ),
+            ),
+        }
+    }
+    insert
+}
+
+/// The columns in diesel's `#[primary_key(column, ...)]`, or `id` like
+/// diesel if it isn't there
+fn extract_primary_key(attrs: &[Attribute], errors: &mut Errors) -> Vec<Ident> {
+    let mut primary_key = vec![format_ident!("id")];
+    for attr in attrs
+        .iter()
+        .filter(|a| a.path().is_ident(DIESEL_PRIMARY_KEY))
+    {
+        match attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated) {
+            Ok(columns) => primary_key = columns.into_iter().collect(),
+            Err(err) => errors.push_error(err),
+        }
+    }
+    primary_key
+}
+
+/// The intermediates in `#[intermediate_insertable(Name, ...)]`, if it is
+/// present
+fn extract_insertable(attrs: &[Attribute], errors: &mut Errors) -> Option<Vec<Ident>> {
+    let mut insertable = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident(INSERTABLE)) {
+        let list = match attr.meta {
+            Meta::List(ref list) => list,
+            _ => {
+                errors.push(attr, format!("expected `#[{}(Name, ...)]`", INSERTABLE));
+                continue;
+            }
+        };
+        match list.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated) {
+            Ok(names) => insertable.get_or_insert_with(Vec::new).extend(names),
+            Err(err) => errors.push_error(err),
+        }
+    }
+    insertable
+}
+
+/// The prefix in `#[intermediate_changeset(Prefix)]`
+fn extract_changeset(attrs: &[Attribute], errors: &mut Errors) -> Option<Ident> {
+    let mut changeset = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident(CHANGESET)) {
+        match attr.parse_args::<Ident>() {
+            Ok(name) => changeset = Some(name),
+            Err(_) => errors.push(attr, format!("expected `#[{}(Prefix)]`", CHANGESET)),
+        }
+    }
+    changeset
+}
+
+/// Extract the naming strategy from `#[intermediate_names(...)]` and
+/// `#[intermediate_constructor(...)]`
+fn extract_naming(attrs: &[Attribute], errors: &mut Errors) -> Naming {
+    let mut naming = Naming::default();
+    let mut style: Option<Ident> = None;
+    for a in attrs.iter().filter(|a| a.path().is_ident(NAMES)) {
+        let list = match a.meta {
+            Meta::List(ref list) => list,
+            _ => {
+                errors.push(
+                    a,
+                    format!(
+                        r#"expected `#[{}(prefix | suffix, Name = "StructName", ...)]`"#,
+                        NAMES
+                    ),
+                );
+                continue;
+            }
+        };
+        let items = match list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated) {
+            Ok(items) => items,
+            Err(err) => {
+                errors.push_error(err);
+                continue;
+            }
+        };
+        for item in items {
+            match item {
+                Meta::Path(ref path) if path.is_ident("prefix") || path.is_ident("suffix") => {
+                    let ident = path.get_ident().unwrap();
+                    match style {
+                        Some(ref existing) if existing != ident => errors.push(
+                            ident,
+                            format!(
+                                "conflicting naming strategies `{}` and `{}`",
+                                existing, ident
+                            ),
+                        ),
+                        _ => {
+                            naming.suffix = ident == "suffix";
+                            style = Some(ident.clone());
+                        }
+                    }
+                }
+                Meta::NameValue(ref nv) if nv.path.get_ident().is_some() => {
+                    let name = nv.path.get_ident().unwrap().clone();
+                    match expr_str(&nv.value).map(|lit| lit.parse::<Ident>()) {
+                        Some(Ok(explicit)) => naming.explicit.push((name, explicit)),
+                        Some(Err(_)) | None => errors.push(
+                            &nv.value,
+                            r#"expected the name of the struct as a string, like `"RustInsert"`"#,
+                        ),
+                    }
+                }
+                ref other => errors.push(
+                    other,
+                    r#"expected `prefix`, `suffix` or `Name = "StructName"`"#,
+                ),
+            }
+        }
+    }
+
+    for a in attrs.iter().filter(|a| a.path().is_ident(CONSTRUCTOR)) {
+        let list = match a.meta {
+            Meta::List(ref list) => list,
+            _ => {
+                errors.push(
+                    a,
+                    format!(r#"expected `#[{}(Name = "fn_name", ...)]`"#, CONSTRUCTOR),
+                );
+                continue;
+            }
+        };
+        for (name, value) in extract_keyed_strs(list, "fn_name", errors) {
+            match value.parse::<Ident>() {
+                Ok(constructor) => naming.constructors.push((name, constructor)),
+                Err(_) => errors.push(
+                    &value,
+                    r#"expected the name of the constructor as a string, like `"persisted"`"#,
+                ),
+            }
+        }
+    }
+    naming
+}
+
+/// The traits to derive on the generated intermediates
+///
+/// `#[intermediate_derive(Debug, New: Deserialize, Captured: Queryable + Eq)]`
+/// derives `Debug` on every intermediate, `Deserialize` on only the `New`
+/// one, and `Queryable` and `Eq` on only the `Captured` one.
+#[derive(Default)]
+struct Derives {
+    all: Vec<Path>,
+    keyed: Vec<(Ident, Vec<Path>)>,
+}
+
+impl Derives {
+    /// The `#[derive(...)]` attribute for the `prefix` intermediate, plus
+    /// any of `implied` that it doesn't already derive, and `table_name` if
+    /// one of them reads it
+    fn attr_for_with(
+        &self,
+        prefix: &str,
+        implied: &[Path],
+        table_name: Option<&LitStr>,
+    ) -> TokenStream {
+        let mut derives = self.all.iter().collect::<Vec<_>>();
+        for (name, paths) in &self.keyed {
+            if name == prefix {
+                derives.extend(paths.iter().filter(|p| !self.all.contains(p)));
+            }
+        }
+        let trait_name = |path: &Path| path.segments.last().map(|s| s.ident.clone());
+        for path in implied {
+            if !derives.iter().any(|d| trait_name(d) == trait_name(path)) {
+                derives.push(path);
+            }
+        }
+        if derives.is_empty() {
+            return quote!();
+        }
+        let reads_table_name = derives.iter().any(|d| {
+            trait_name(d).is_some_and(|name| TABLE_NAME_DERIVES.iter().any(|t| name == t))
+        });
+        match table_name {
+            Some(table_name) if reads_table_name => quote! {
+                #[derive(#(#derives),*)]
+                #[table_name = #table_name]
+            },
+            _ => quote!(#[derive(#(#derives),*)]),
+        }
+    }
+}
+
+/// The other attributes to put on the generated intermediates
+///
+/// `#[intermediate_attr(serde(rename_all = "camelCase"))]` goes on every
+/// intermediate, `#[intermediate_attr(New, serde(deny_unknown_fields))]` only
+/// on the `New` one.
+#[derive(Default)]
+struct ContainerAttrs {
+    all: Vec<Meta>,
+    keyed: Vec<(Ident, Meta)>,
+}
+
+impl ContainerAttrs {
+    /// The attributes for the `New` or `prefix` intermediate
+    fn attrs_for(&self, prefix: &str) -> TokenStream {
+        let keyed = self
+            .keyed
+            .iter()
+            .filter(|&(name, _)| name == prefix)
+            .map(|(_, meta)| meta);
+        let metas = self.all.iter().chain(keyed);
+        quote!(#(#[#metas])*)
+    }
+}
+
+/// The contents of `#[intermediate_attr(...)]`: `attr` or `Name, attr`
+enum ContainerAttr {
+    All(Meta),
+    Keyed(Ident, Meta),
+}
+
+impl Parse for ContainerAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attr = if input.peek(Ident) && input.peek2(Token![,]) {
+            let name = input.parse()?;
+            input.parse::<Token![,]>()?;
+            ContainerAttr::Keyed(name, input.parse()?)
+        } else {
+            ContainerAttr::All(input.parse()?)
+        };
+        input.parse::<Option<Token![,]>>()?;
+        Ok(attr)
+    }
+}
+
+/// The attributes in every `#[intermediate_attr(...)]`
+fn extract_attrs(attrs: &[Attribute], errors: &mut Errors) -> ContainerAttrs {
+    let mut container_attrs = ContainerAttrs::default();
+    for a in attrs.iter().filter(|a| a.path().is_ident(ATTR)) {
+        match a.parse_args::<ContainerAttr>() {
+            Ok(ContainerAttr::All(meta)) => container_attrs.all.push(meta),
+            Ok(ContainerAttr::Keyed(name, meta)) => container_attrs.keyed.push((name, meta)),
+            Err(_) => errors.push(
+                a,
+                format!(
+                    "expected `#[{}(attribute)]` or `#[{}(Name, attribute)]`",
+                    ATTR, ATTR
+                ),
+            ),
+        }
+    }
+    container_attrs
+}
+
+/// One item in `#[intermediate_derive(...)]`: `Trait` or `Name: Trait + ...`
+enum DeriveItem {
+    All(Path),
+    Keyed(Ident, Vec<Path>),
+}
+
+impl Parse for DeriveItem {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path: Path = input.parse()?;
+        if !input.peek(Token![:]) {
+            return Ok(DeriveItem::All(path));
+        }
+        let name = path.require_ident()?.clone();
+        input.parse::<Token![:]>()?;
+        let paths = Punctuated::<Path, Token![+]>::parse_separated_nonempty(input)?;
+        Ok(DeriveItem::Keyed(name, paths.into_iter().collect()))
+    }
+}
+
+/// The traits in every `#[intermediate_derive(...)]`
+fn extract_derives(attrs: &[Attribute], errors: &mut Errors) -> Derives {
+    let mut derives = Derives::default();
+    for a in attrs.iter().filter(|a| a.path().is_ident(DERIVE)) {
+        let list = match a.meta {
+            Meta::List(ref list) => list,
+            _ => {
+                errors.push(
+                    a,
+                    format!("expected `#[{}(Trait, Name: Trait, ...)]`", DERIVE),
+                );
+                continue;
+            }
+        };
+        match list.parse_args_with(Punctuated::<DeriveItem, Token![,]>::parse_terminated) {
+            Ok(items) => {
+                for item in items {
+                    match item {
+                        DeriveItem::All(path) => derives.all.push(path),
+                        DeriveItem::Keyed(name, paths) => derives.keyed.push((name, paths)),
+                    }
+                }
+            }
+            Err(err) => errors.push_error(err),
+        }
+    }
+    derives
+}
+
+/// Report `name` if it isn't `New`, the prefix of an intermediate or one of
+/// `extra`, and whether it is
+fn check_intermediate_name(
+    name: &Ident,
+    shape: &Shape,
+    extra: &[&Ident],
+    errors: &mut Errors,
+) -> bool {
+    let prefixes = shape.prefixes();
+    if name == "New" || prefixes.iter().any(|p| name == p) || extra.contains(&name) {
+        true
+    } else {
+        let known = Some("New".to_string())
+            .into_iter()
+            .chain(prefixes)
+            .chain(extra.iter().map(|e| e.to_string()))
+            .map(|p| format!("`{}`", p))
+            .collect::<Vec<_>>();
+        errors.push(
+            name,
+            format!(
+                "unknown intermediate `{}`, expected one of {}",
+                name,
+                known.join(", ")
+            ),
+        );
+        false
+    }
+}
+
+enum ExcludeAttr {
+    /// A field that is excluded from the `New` item
+    Excluded,
+    /// A field that is excluded from the `New` item but included in every
+    /// named item
+    Intermediate(Vec<Ident>),
+    Included,
+}
+
+/// Parse the attributes on fields to get a list fields that should be excluded
+fn extract_intermediates(fields: &Fields, errors: &mut Errors) -> IntermediateFields {
+    let mut intermediates = IntermediateFields {
+        style: match *fields {
+            Fields::Named(_) => FieldsStyle::Named,
+            Fields::Unnamed(_) => FieldsStyle::Tuple,
+            Fields::Unit => FieldsStyle::Unit,
+        },
+        ..IntermediateFields::default()
+    };
+    for (i, f) in fields.iter().enumerate() {
+        use ExcludeAttr::*;
+        let (status, default) = field_status(f, errors);
+        match status {
+            Excluded => intermediates.excluded_at_least_once.push(i),
+            Intermediate(intermediate_prefixes) => {
+                for prefix in intermediate_prefixes {
+                    intermediates.prefix_entry(&prefix.to_string()).push(i);
+                }
+                intermediates.excluded_at_least_once.push(i);
+            }
+            Included => intermediates.common_fields.push(i),
+        }
+        if let Some(default) = default {
+            intermediates.defaults.push((i, default));
+        }
+
+        for a in f.attrs.iter().filter(|a| a.path().is_ident(ONLY)) {
+            let names = match a.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated) {
+                Ok(names) if !names.is_empty() => names,
+                _ => {
+                    errors.push(a, format!("expected `#[{}(Name, ...)]`", ONLY));
+                    continue;
+                }
+            };
+            for name in names {
+                if name == "New" {
+                    errors.push(
+                        &name,
+                        format!(
+                            "the New intermediate has every field that isn't excluded, \
+                             it can't be used with #[{}]",
+                            ONLY
+                        ),
+                    );
+                    continue;
+                }
+                let fields = intermediates.only_entry(&name);
+                if !fields.contains(&i) {
+                    fields.push(i);
+                }
+            }
+        }
+
+        for a in f.attrs.iter().filter(|a| a.path().is_ident(FIELD_ATTR)) {
+            match a.parse_args::<FieldAttr>() {
+                Ok(FieldAttr { name, meta }) => intermediates.field_attrs.push((i, name, meta)),
+                Err(_) => errors.push(a, format!("expected `#[{}(Name, attribute)]`", FIELD_ATTR)),
+            }
+        }
+
+        let mut field_without_attr = f.clone();
+        for to_strip in [EXCLUDE, ONLY, FIELD_ATTR] {
+            field_without_attr.attrs = strip_attr(&field_without_attr.attrs, to_strip);
+        }
+        intermediates.fields.push(field_without_attr);
+    }
+    intermediates
+}
+
+/// Parse the fields of every variant of an enum
+///
+/// Every variant knows about every prefix used anywhere in the enum, so that
+/// all of them end up in every generated enum.
+fn extract_variant_intermediates<'a>(
+    data: &'a DataEnum,
+    errors: &mut Errors,
+) -> Vec<VariantFields<'a>> {
+    let mut variants = data
+        .variants
+        .iter()
+        .map(|variant| VariantFields {
+            ident: &variant.ident,
+            attrs: variant.attrs.iter().collect(),
+            discriminant: variant.discriminant.as_ref().map(|d| &d.1),
+            fields: extract_intermediates(&variant.fields, errors),
+        })
+        .collect::<Vec<_>>();
+
+    let mut prefixes: Vec<String> = Vec::new();
+    for variant in &variants {
+        for (prefix, _) in &variant.fields.prefix_excluded {
+            if !prefixes.contains(prefix) {
+                prefixes.push(prefix.clone());
+            }
+        }
+    }
+    let mut only_names: Vec<Ident> = Vec::new();
+    for variant in &variants {
+        for (name, _) in &variant.fields.included_only {
+            if !only_names.contains(name) {
+                only_names.push(name.clone());
+            }
+        }
+    }
+    for variant in &mut variants {
+        variant.fields.prefix_excluded = prefixes
+            .iter()
+            .map(|prefix| {
+                let fields = variant.fields.prefix_fields(prefix).unwrap_or(&[]);
+                (prefix.clone(), fields.to_vec())
+            })
+            .collect();
+        variant.fields.included_only = only_names
+            .iter()
+            .map(|name| {
+                let fields = variant.fields.only_fields(&name.to_string());
+                (name.clone(), fields.unwrap_or(&[]).to_vec())
+            })
+            .collect();
+    }
+
+    variants
+}
+
+/// One intermediate in `#[intermediates(...)]`: `Name(exclude(field, ...))`
+/// or `Name(include(field, ...))`
+struct IntermediateSpec {
+    name: Ident,
+    /// `include` or `exclude`
+    kind: Ident,
+    fields: Vec<Member>,
+}
+
+impl Parse for IntermediateSpec {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let content;
+        syn::parenthesized!(content in input);
+        let kind: Ident = content.parse()?;
+        if kind != "include" && kind != "exclude" {
+            return Err(syn::Error::new_spanned(
+                kind,
+                "expected `exclude(field, ...)` or `include(field, ...)`",
+            ));
+        }
+        let members;
+        syn::parenthesized!(members in content);
+        let fields = Punctuated::<Member, Token![,]>::parse_terminated(&members)?;
+        content.parse::<Option<Token![,]>>()?;
+        Ok(IntermediateSpec {
+            name,
+            kind,
+            fields: fields.into_iter().collect(),
+        })
+    }
+}
+
+/// The intermediates declared in every `#[intermediates(...)]`
+fn extract_specs(attrs: &[Attribute], errors: &mut Errors) -> Vec<IntermediateSpec> {
+    let mut specs: Vec<IntermediateSpec> = Vec::new();
+    for a in attrs.iter().filter(|a| a.path().is_ident(SPECS)) {
+        let parsed = a.parse_args_with(Punctuated::<IntermediateSpec, Token![,]>::parse_terminated);
+        match parsed {
+            Ok(parsed) => {
+                for spec in parsed {
+                    if specs.iter().any(|s| s.name == spec.name) {
+                        errors.push(
+                            &spec.name,
+                            format!("`{}` is declared more than once", spec.name),
+                        );
+                    } else {
+                        specs.push(spec);
+                    }
+                }
+            }
+            Err(err) => errors.push_error(err),
+        }
+    }
+    specs
+}
+
+/// Group the fields the way that `#[intermediates(...)]` says, on top of
+/// whatever the field attributes already did
+///
+/// `New(exclude(...))` is the same as `#[intermediate_exclude]` on each
+/// field, `Name(exclude(...))` puts every other field that is excluded from
+/// `New` into `Name` like `#[intermediate_exclude(Name)]`, and
+/// `Name(include(...))` is `#[intermediate_only(Name)]`.
+fn apply_specs(
+    specs: &[IntermediateSpec],
+    intermediates: &mut IntermediateFields,
+    errors: &mut Errors,
+) {
+    let mut resolved = Vec::new();
+    for spec in specs {
+        let mut indexes = Vec::new();
+        for member in &spec.fields {
+            let index = intermediates.fields.iter().enumerate().position(|(i, f)| {
+                match (member, &f.ident) {
+                    (Member::Named(name), Some(ident)) => name == ident,
+                    (Member::Unnamed(index), None) => index.index as usize == i,
+                    _ => false,
+                }
+            });
+            match index {
+                Some(index) => indexes.push(index),
+                None => errors.push(
+                    member,
+                    format!("unknown field `{}`", member.to_token_stream()),
+                ),
+            }
+        }
+        resolved.push((spec, indexes));
+    }
+
+    // everything else is relative to `New`, so it goes first
+    resolved.sort_by_key(|&(spec, _)| spec.name != "New");
+    for (spec, indexes) in resolved {
+        if spec.name == "New" {
+            if spec.kind == "include" {
+                errors.push(
+                    &spec.kind,
+                    "the New intermediate has every field that isn't excluded, use `exclude`",
+                );
+                continue;
+            }
+            for i in indexes {
+                if let Some(position) = intermediates.common_fields.iter().position(|&f| f == i) {
+                    intermediates.common_fields.remove(position);
+                    intermediates.excluded_at_least_once.push(i);
+                }
+            }
+            intermediates.excluded_at_least_once.sort();
+        } else if spec.kind == "include" {
+            let fields = intermediates.only_entry(&spec.name);
+            fields.extend(indexes);
+            fields.sort();
+            fields.dedup();
+        } else {
+            for (member, &i) in spec.fields.iter().zip(&indexes) {
+                if intermediates.common_fields.contains(&i) {
+                    errors.push(
+                        member,
+                        format!(
+                            "every intermediate has the fields of `New`, \
+                             exclude this field from `New` too to leave it out of `{}`",
+                            spec.name
+                        ),
+                    );
+                }
+            }
+            let kept = intermediates
+                .excluded_at_least_once
+                .iter()
+                .cloned()
+                .filter(|i| !indexes.contains(i))
+                .collect::<Vec<_>>();
+            let fields = intermediates.prefix_entry(&spec.name.to_string());
+            fields.extend(kept);
+            fields.sort();
+            fields.dedup();
+        }
+    }
+}
+
+/// How the fields of a struct are declared
+#[derive(Clone, Copy, Default, PartialEq)]
+enum FieldsStyle {
+    /// `struct S { a: i32 }`
+    #[default]
+    Named,
+    /// `struct S(i32);`
+    Tuple,
+    /// `struct S;`
+    Unit,
+}
+
+/// A list of all the fields on an original struct, grouped by their status
+///
+/// Fields are referred to by their position in the original struct, since
+/// tuple struct fields don't have any other identity.
+#[derive(Default)]
+struct IntermediateFields {
+    /// Every field of the original struct, in declaration order and without
+    /// any `#[intermediate_exclude]` or `#[intermediate_field_attr]`
+    /// attributes
+    fields: Vec<Field>,
+    style: FieldsStyle,
+    /// The fields that never have an `#[intermediate_exclude]` attribute on them
+    common_fields: Vec<usize>,
+    /// Every exclude annotation (either `#[intermediate_exclude]` or
+    /// `#[intermediate_exclude(Prefix)]`) will add to this list
+    excluded_at_least_once: Vec<usize>,
+    /// Fields that are excluded with a prefix are grouped by prefix here, in
+    /// the order that each prefix is first used
+    prefix_excluded: Vec<(String, Vec<usize>)>,
+    /// Intermediates that have nothing but the fields marked with
+    /// `#[intermediate_only(Name)]`, in the order that each name is first used
+    included_only: Vec<(Ident, Vec<usize>)>,
+    /// Attributes that only go on one intermediate's copy of a field, from
+    /// `#[intermediate_field_attr(Name, attr)]`
+    field_attrs: Vec<(usize, Ident, Meta)>,
+    /// Excluded fields that the constructors fill in themselves instead of
+    /// taking a parameter, from `#[intermediate_exclude(default = "...")]`
+    defaults: Vec<(usize, Expr)>,
+}
+
+impl IntermediateFields {
+    /// All groups of items that are field subsets of the current prefix
+    ///
+    /// So given a struct like:
+    ///
+    /// ```rust,ignore
+    /// #[derive(DieselIntermediate)]
+    /// struct Big {
+    ///     #[intermediate_exclude]
+    ///     id: i32,
+    ///     #[intermediate_exclude]
     ///     meta: i32,
-    ///     #[intermediate_exclude(Outer)],
+    ///     #[intermediate_exclude(Outer)]
     ///     outer: i32,
-    ///     #[intermediate_exclude(Outer, Inner)],
+    ///     #[intermediate_exclude(Outer, Inner)]
     ///     outer_inner: i32,
-    ///     #[intermediate_exclude(Inner)],
+    ///     #[intermediate_exclude(Inner)]
     ///     inner: i32,
     ///
     ///     common: i32,
     /// }
     /// ```
     ///
-    /// (after diesel-derive-intermediate supports multiple intermediates)
-    ///
     /// This would yield the following items:
     ///
     /// * field_differences("Outer")
@@ -497,12 +2646,12 @@ impl IntermediateFields {
     ///   * `New, [outer_inner, inner]`
     ///
     /// See also `field_difference_for_full_iter`
-    fn field_differences(&self, current_prefix: &str) -> Vec<(String, Vec<&Field>, Vec<&Field>)> {
+    fn field_differences(&self, current_prefix: &str) -> Vec<(String, Vec<usize>, Vec<usize>)> {
         // except for the current fields and the extra filter, this is
         // identical to the function below
-        let current_fields = &self.prefix_excluded[current_prefix];
+        let current_fields = self.fields_of(current_prefix);
 
-        self._field_differences_inner(current_prefix, current_fields)
+        self._field_differences_inner(current_prefix, &current_fields)
     }
 
     /// All groups of items that are field subsets of the complete item
@@ -512,23 +2661,21 @@ impl IntermediateFields {
     /// ```rust,ignore
     /// #[derive(DieselIntermediate)]
     /// struct Big {
-    ///     #[intermediate_exclude],
+    ///     #[intermediate_exclude]
     ///     id: i32,
-    ///     #[intermediate_exclude],
+    ///     #[intermediate_exclude]
     ///     meta: i32,
-    ///     #[intermediate_exclude(Outer)],
+    ///     #[intermediate_exclude(Outer)]
     ///     outer: i32,
-    ///     #[intermediate_exclude(Outer, Inner)],
+    ///     #[intermediate_exclude(Outer, Inner)]
     ///     outer_inner: i32,
-    ///     #[intermediate_exclude(Inner)],
+    ///     #[intermediate_exclude(Inner)]
     ///     inner: i32,
     ///
     ///     common: i32,
     /// }
     /// ```
     ///
-    /// (after diesel-derive-intermediate supports multiple intermediates)
-    ///
     /// This would yield the following items:
     ///
     /// * field_difference_for_full_iter()
@@ -537,33 +2684,29 @@ impl IntermediateFields {
     ///   * `Inner, [id, meta, outer]`
     ///
     /// See also `field_differences`
-    fn field_differences_full(&self) -> Vec<(String, Vec<&Field>, Vec<&Field>)> {
-        let current_fields = &self.excluded_at_least_once;
+    fn field_differences_full(&self) -> Vec<(String, Vec<usize>, Vec<usize>)> {
+        let current_fields = self.full_fields();
 
-        self._field_differences_inner("__", current_fields)
+        self._field_differences_inner("__", &current_fields)
     }
 
-    fn _field_differences_inner<'s>(
-        &'s self,
+    fn _field_differences_inner(
+        &self,
         current_prefix: &str,
-        current_fields: &'s [Field],
-    ) -> Vec<(String, Vec<&'s Field>, Vec<&'s Field>)> {
-        self.prefix_excluded
-            .iter()
-            .chain(vec![(&"New".to_string(), &self.common_fields)].into_iter())
-            .filter(|&(prefix, _)| prefix != current_prefix)
-            .filter_map(|(prefix, other_excluded_fields)| {
-                let prefix = prefix.clone();
-                let other_fields: HashSet<&Field> =
-                    HashSet::from_iter(other_excluded_fields.iter());
-                let field_difference = current_fields
-                    .iter()
-                    .filter(|f| !other_fields.contains(f))
-                    .collect::<Vec<_>>();
-                let field_sames = current_fields
+        current_fields: &[usize],
+    ) -> Vec<(String, Vec<usize>, Vec<usize>)> {
+        let prefixes = self.prefix_excluded.iter().map(|(p, _)| p.clone());
+        let only = self.included_only.iter().map(|(name, _)| name.to_string());
+        Some("New".to_string())
+            .into_iter()
+            .chain(prefixes)
+            .chain(only)
+            .filter(|prefix| prefix != current_prefix)
+            .filter_map(|prefix| {
+                let other_fields = self.fields_of(&prefix);
+                let (field_sames, field_difference): (Vec<usize>, Vec<usize>) = current_fields
                     .iter()
-                    .filter(|f| other_fields.contains(f))
-                    .collect::<Vec<_>>();
+                    .partition(|f| other_fields.contains(f));
 
                 if !field_difference.is_empty() {
                     Some((prefix, field_difference, field_sames))
@@ -573,39 +2716,380 @@ impl IntermediateFields {
             })
             .collect()
     }
+
+    /// The fields that are excluded into the `prefix` intermediate
+    fn prefix_fields(&self, prefix: &str) -> Option<&[usize]> {
+        self.prefix_excluded
+            .iter()
+            .find(|&(p, _)| p == prefix)
+            .map(|(_, fields)| &fields[..])
+    }
+
+    /// The fields of the `#[intermediate_only(name)]` intermediate
+    fn only_fields(&self, name: &str) -> Option<&[usize]> {
+        self.included_only
+            .iter()
+            .find(|&(n, _)| n == name)
+            .map(|(_, fields)| &fields[..])
+    }
+
+    fn only_entry(&mut self, name: &Ident) -> &mut Vec<usize> {
+        let position = match self.included_only.iter().position(|(n, _)| n == name) {
+            Some(position) => position,
+            None => {
+                self.included_only.push((name.clone(), Vec::new()));
+                self.included_only.len() - 1
+            }
+        };
+        &mut self.included_only[position].1
+    }
+
+    fn prefix_entry(&mut self, prefix: &str) -> &mut Vec<usize> {
+        let position = match self.prefix_excluded.iter().position(|(p, _)| p == prefix) {
+            Some(position) => position,
+            None => {
+                self.prefix_excluded.push((prefix.to_string(), Vec::new()));
+                self.prefix_excluded.len() - 1
+            }
+        };
+        &mut self.prefix_excluded[position].1
+    }
+
+    /// The fields of the full struct, in the order they are declared
+    fn full_fields(&self) -> Vec<usize> {
+        (0..self.fields.len()).collect()
+    }
+
+    /// The fields of the `New` or a prefixed intermediate, in the order they
+    /// are declared in the original struct
+    ///
+    /// Keeping the original order matters for anything positional, like
+    /// diesel's `Queryable` or tuple structs.
+    fn fields_of(&self, prefix: &str) -> Vec<usize> {
+        if let Some(only_fields) = self.only_fields(prefix) {
+            return only_fields.to_vec();
+        }
+        match self.prefix_fields(prefix) {
+            Some(extra_fields) => {
+                let mut fields = extra_fields
+                    .iter()
+                    .chain(&self.common_fields)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                fields.sort();
+                fields
+            }
+            None => self.common_fields.clone(),
+        }
+    }
+
+    /// The fields of the `New` or `prefix` intermediate that make up
+    /// `primary_key`, if it has all of them
+    fn key_fields<'k>(&self, prefix: &str, primary_key: &'k [Ident]) -> Option<Vec<&'k Ident>> {
+        let fields = self.fields_of(prefix);
+        let has = |column: &Ident| {
+            fields
+                .iter()
+                .any(|&i| self.fields[i].ident.as_ref() == Some(column))
+        };
+        if !primary_key.is_empty() && primary_key.iter().all(has) {
+            Some(primary_key.iter().collect())
+        } else {
+            None
+        }
+    }
+
+    /// The braces or parentheses containing `fields`, in a struct
+    /// declaration or enum variant
+    fn fields_body(
+        &self,
+        fields: &[usize],
+        field_types: FieldTypes,
+        attrs_for: &str,
+    ) -> TokenStream {
+        let fields = fields.iter().map(|&i| {
+            let mut field = self.fields[i].clone();
+            for (_, _, meta) in self
+                .field_attrs
+                .iter()
+                .filter(|&(index, name, _)| *index == i && name == attrs_for)
+            {
+                field.attrs.push(parse_quote!(#[#meta]));
+            }
+            let ty = field.ty;
+            field.ty = match field_types {
+                FieldTypes::AsDeclared => ty,
+                FieldTypes::Borrowed(lifetime) => parse_quote!(&#lifetime #ty),
+                FieldTypes::Optional => parse_quote!(::std::option::Option<#ty>),
+            };
+            field
+        });
+        match self.style {
+            FieldsStyle::Named => quote! { { #(#fields),* } },
+            FieldsStyle::Tuple => quote! { ( #(#fields),* ) },
+            FieldsStyle::Unit => quote! {},
+        }
+    }
+
+    /// The fields of the full struct, or of the `prefix` intermediate
+    fn fields_for(&self, prefix: Option<&str>) -> Vec<usize> {
+        match prefix {
+            Some(prefix) => self.fields_of(prefix),
+            None => self.full_fields(),
+        }
+    }
+
+    /// Build `this_name` out of the `base` parameter and the
+    /// `different_fields` parameters
+    fn construct_from_base(
+        &self,
+        this_name: &Ident,
+        this_prefix: Option<&str>,
+        from_prefix: &str,
+        different_fields: &[usize],
+    ) -> TokenStream {
+        let this_fields = self.fields_for(this_prefix);
+        let from_fields = self.fields_of(from_prefix);
+        let field_inits = this_fields.iter().map(|&i| {
+            let member = self.member(i, &this_fields);
+            if let (true, Some(default)) = (different_fields.contains(&i), self.default_of(i)) {
+                quote! { #member: #default }
+            } else if different_fields.contains(&i) {
+                let param = self.param_name(i);
+                quote! { #member: #param }
+            } else {
+                let from_member = self.member(i, &from_fields);
+                quote! { #member: base.#from_member }
+            }
+        });
+        quote! {
+            #this_name {
+                #(#field_inits),*
+            }
+        }
+    }
+
+    /// A match arm that destructures `from_path` and builds `this_path` out
+    /// of its fields and the `different_fields` parameters
+    fn construct_from_base_arm(
+        &self,
+        this_path: &TokenStream,
+        this_prefix: Option<&str>,
+        from_path: &TokenStream,
+        from_prefix: &str,
+        different_fields: &[usize],
+    ) -> (TokenStream, TokenStream) {
+        let this_fields = self.fields_for(this_prefix);
+        let from_fields = self.fields_of(from_prefix);
+        let mut bindings = Vec::new();
+        let field_inits = this_fields
+            .iter()
+            .map(|&i| {
+                let member = self.member(i, &this_fields);
+                if let (true, Some(default)) = (different_fields.contains(&i), self.default_of(i)) {
+                    quote! { #member: #default }
+                } else if different_fields.contains(&i) {
+                    let param = self.param_name(i);
+                    quote! { #member: #param }
+                } else {
+                    let from_member = self.member(i, &from_fields);
+                    let binding = format_ident!("__base_{}", self.param_name(i));
+                    bindings.push(quote! { #from_member: #binding });
+                    quote! { #member: #binding }
+                }
+            })
+            .collect::<Vec<_>>();
+        (
+            quote! { #from_path { #(#bindings,)* .. } },
+            quote! { #this_path { #(#field_inits),* } },
+        )
+    }
+
+    /// A match arm that destructures `from_path` and builds `this_path` out
+    /// of the fields they share, cloning them if they are borrowed
+    fn project_arm(
+        &self,
+        from_path: &TokenStream,
+        from_prefix: Option<&str>,
+        this_path: &TokenStream,
+        this_prefix: &str,
+        projection: Projection,
+    ) -> (TokenStream, TokenStream) {
+        let this_fields = self.fields_of(this_prefix);
+        let from_fields = self.fields_for(from_prefix);
+        let mut bindings = Vec::new();
+        let field_inits = this_fields
+            .iter()
+            .map(|&i| {
+                let member = self.member(i, &this_fields);
+                let from_member = self.member(i, &from_fields);
+                let binding = format_ident!("__base_{}", self.param_name(i));
+                match projection {
+                    Projection::Moved => {
+                        bindings.push(quote! { #from_member: #binding });
+                        quote! { #member: #binding }
+                    }
+                    Projection::Cloned => {
+                        bindings.push(quote! { #from_member: ref #binding });
+                        quote! { #member: ::std::clone::Clone::clone(#binding) }
+                    }
+                    Projection::Borrowed => {
+                        bindings.push(quote! { #from_member: ref #binding });
+                        quote! { #member: #binding }
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        (
+            quote! { #from_path { #(#bindings,)* .. } },
+            quote! { #this_path { #(#field_inits),* } },
+        )
+    }
+
+    /// How to access field `index` of a struct containing `within`
+    fn member(&self, index: usize, within: &[usize]) -> Member {
+        match self.fields[index].ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => {
+                let position = within.iter().position(|&i| i == index).unwrap();
+                Member::Unnamed(syn::Index::from(position))
+            }
+        }
+    }
+
+    /// The expression that fills in field `index`, if it has a default
+    fn default_of(&self, index: usize) -> Option<&Expr> {
+        self.defaults
+            .iter()
+            .find(|&&(i, _)| i == index)
+            .map(|(_, default)| default)
+    }
+
+    /// The constructor parameters needed to fill in `different_fields`:
+    /// the fields without a default
+    fn params(&self, different_fields: &[usize]) -> Vec<usize> {
+        different_fields
+            .iter()
+            .cloned()
+            .filter(|&i| self.default_of(i).is_none())
+            .collect()
+    }
+
+    /// The name of the constructor parameter for field `index`
+    fn param_name(&self, index: usize) -> Ident {
+        match self.fields[index].ident {
+            Some(ref ident) => ident.clone(),
+            None => format_ident!("field_{}", index),
+        }
+    }
+
+    /// The field as a function parameter: no visibility and no attributes
+    fn param_form(&self, index: usize) -> TokenStream {
+        let param = self.param_name(index);
+        let ty = &self.fields[index].ty;
+        quote! { #param: #ty }
+    }
 }
 
-fn field_status(field: &Field) -> ExcludeAttr {
+/// Which intermediates the field is excluded from, and the expression that
+/// fills it in if it has a `default`
+fn field_status(field: &Field, errors: &mut Errors) -> (ExcludeAttr, Option<Expr>) {
     use ExcludeAttr::*;
-    for a in &field.attrs {
-        match a.value {
-            MetaItem::Word(ref ident) if ident == EXCLUDE => {
-                return Excluded(field.clone());
-            }
-            MetaItem::List(ref ident, ref vals) if ident == EXCLUDE && vals.len() == 1 => {
-                // but, if the field is marked with some prefix, then we
-                // want to store it to be used in the Prefix struct
-                if let Some(&NestedMetaItem::MetaItem(MetaItem::Word(ref val))) = vals.get(0) {
-                    let mut field_without_attr = (*field).clone();
-                    field_without_attr.attrs = strip_attr(&field.attrs, EXCLUDE);
-                    return Intermediate(val.as_ref(), field_without_attr);
-                } else {
-                    panic!(
-                        "Unexpected shape for attribute: {} over {}",
-                        quote!(#vals),
-                        quote!(#field)
-                    );
+    for a in field.attrs.iter().filter(|a| a.path().is_ident(EXCLUDE)) {
+        let list = match a.meta {
+            Meta::Path(_) => return (Excluded, None),
+            Meta::List(ref list) => list,
+            Meta::NameValue(_) => {
+                errors.push(
+                    a,
+                    format!("expected `#[{}]` or `#[{}(Prefix, ...)]`", EXCLUDE, EXCLUDE),
+                );
+                continue;
+            }
+        };
+        let vals = match list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated) {
+            Ok(vals) => vals,
+            Err(err) => {
+                errors.push_error(err);
+                continue;
+            }
+        };
+        if vals.is_empty() {
+            errors.push(
+                a,
+                format!(
+                    "empty `#[{}()]`, use `#[{}]` to exclude the field from the New struct",
+                    EXCLUDE, EXCLUDE
+                ),
+            );
+            continue;
+        }
+
+        // but, if the field is marked with some prefixes, then we want to
+        // store it to be used in each of the Prefix structs
+        let mut prefixes: Vec<Ident> = Vec::new();
+        let mut default: Option<Expr> = None;
+        for val in &vals {
+            if val.path().is_ident("default") {
+                let expr = match *val {
+                    Meta::Path(_) => Some(parse_quote!(::std::default::Default::default())),
+                    Meta::NameValue(ref nv) => {
+                        match expr_str(&nv.value).map(|lit| lit.parse::<Expr>()) {
+                            Some(Ok(expr)) => Some(expr),
+                            Some(Err(_)) | None => {
+                                errors.push(
+                                    &nv.value,
+                                    r#"expected the default as a string, like `"Utc::now()"`"#,
+                                );
+                                None
+                            }
+                        }
+                    }
+                    Meta::List(_) => {
+                        errors.push(val, r#"expected `default` or `default = "expression"`"#);
+                        None
+                    }
+                };
+                match expr {
+                    Some(_) if default.is_some() => {
+                        errors.push(val, "`default` is listed more than once")
+                    }
+                    Some(expr) => default = Some(expr),
+                    None => {}
                 }
+                continue;
             }
-            MetaItem::List(ref ident, ref vals) if ident == EXCLUDE => panic!(
-                "Cannot handle more than one intermediate type yet: {}",
-                quote! { #ident(#(#vals),*) }
-            ),
-            MetaItem::NameValue(..) | MetaItem::Word(..) | MetaItem::List(..) => {
-                // If it's not an EXCLUDE attr we don't need to do anything to it
+            match val.path().get_ident() {
+                Some(prefix) if !matches!(*val, Meta::Path(_)) => errors.push(
+                    val,
+                    format!("expected an intermediate name like `{}`", prefix),
+                ),
+                Some(prefix) if prefix == "New" => errors.push(
+                    prefix,
+                    "the New intermediate is always generated, use `#[intermediate_exclude]` \
+                     to exclude a field from it",
+                ),
+                Some(prefix) if prefixes.contains(prefix) => {
+                    errors.push(prefix, format!("`{}` is listed more than once", prefix))
+                }
+                Some(prefix) => prefixes.push(prefix.clone()),
+                None => errors.push(
+                    val,
+                    format!(
+                        "expected an intermediate name like `#[{}(Prefix)]`",
+                        EXCLUDE
+                    ),
+                ),
+            }
+        }
+        if prefixes.len() + default.iter().count() == vals.len() {
+            if prefixes.is_empty() {
+                return (Excluded, default);
             }
+            return (Intermediate(prefixes), default);
         }
     }
-    // if we never encountered an EXCLUDE attr then it's still included
-    Included
+    // if we never encountered an EXCLUDE attr (or it was malformed and has
+    // already been reported) then it's still included
+    (Included, None)
 }
@@ -1,6 +1,6 @@
 // this is a compile-pass test
 #![allow(dead_code)]
-#![cfg_attr(feature = "cargo-clippy", allow(no_effect))]
+#![allow(clippy::no_effect)]
 
 #[macro_use]
 extern crate diesel_derive_intermediate;
@@ -0,0 +1,50 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+#[intermediate_builder]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub name: String,
+    pub life_cycle_stage: i32,
+    pub r#type: &'static str,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+#[intermediate_builder]
+pub struct Sample<T>(#[intermediate_exclude] pub i32, pub T);
+
+#[test]
+fn builders_set_every_field() {
+    let new = NewRust::builder()
+        .life_cycle_stage(2)
+        .name("puccinia".to_string())
+        .r#type("stem")
+        .build()
+        .unwrap();
+    assert_eq!(
+        new,
+        NewRust {
+            name: "puccinia".to_string(),
+            life_cycle_stage: 2,
+            r#type: "stem",
+        }
+    );
+
+    let sample = NewSample::builder().field_1(vec![1]).build();
+    assert_eq!(sample, Ok(NewSample(vec![1])));
+}
+
+#[test]
+fn unset_fields_are_reported() {
+    let err = NewRust::builder().name("puccinia".to_string()).build();
+    assert_eq!(
+        err,
+        Err("`life_cycle_stage` was not set on `NewRustBuilder`")
+    );
+}
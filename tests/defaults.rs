@@ -0,0 +1,97 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+fn next_version() -> u32 {
+    1
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    #[intermediate_exclude(default)]
+    pub deleted: bool,
+    #[intermediate_exclude(Captured, default = "next_version()")]
+    pub version: u32,
+    pub life_cycle_stage: i32,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub struct Tag(#[intermediate_exclude(default)] pub Vec<String>, pub String);
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Found {
+        #[intermediate_exclude]
+        id: i32,
+        #[intermediate_exclude(default)]
+        seen: u32,
+        rust_count: i32,
+    },
+    Reset {
+        #[intermediate_exclude]
+        id: i32,
+    },
+}
+
+#[test]
+fn defaulted_fields_are_not_parameters() {
+    let new = NewRust {
+        life_cycle_stage: 2,
+    };
+    assert_eq!(
+        Rust::from_new_rust(1, 3, new.clone()),
+        Rust {
+            id: 1,
+            mycologist_id: 3,
+            deleted: false,
+            version: 1,
+            life_cycle_stage: 2,
+        }
+    );
+
+    let captured = CapturedRust::from_new_rust(3, new);
+    assert_eq!(
+        captured,
+        CapturedRust {
+            mycologist_id: 3,
+            version: 1,
+            life_cycle_stage: 2,
+        }
+    );
+
+    // fields that the intermediate already has are kept, not defaulted
+    let captured = CapturedRust {
+        version: 7,
+        ..captured
+    };
+    assert_eq!(Rust::from((4, captured)).version, 7);
+}
+
+#[test]
+fn fully_defaulted_constructors_take_just_the_base() {
+    let tag = Tag::from_new_tag(NewTag("rust".to_string()));
+    assert_eq!(tag, Tag(Vec::new(), "rust".to_string()));
+    assert_eq!(Tag::from(NewTag("smut".to_string())).1, "smut");
+}
+
+#[test]
+fn enum_variants_default_their_own_fields() {
+    assert_eq!(
+        Event::from_new_event_found(1, NewEvent::Found { rust_count: 3 }),
+        Ok(Event::Found {
+            id: 1,
+            seen: 0,
+            rust_count: 3,
+        })
+    );
+    assert_eq!(
+        Event::from_new_event_reset(2, NewEvent::Reset {}),
+        Ok(Event::Reset { id: 2 })
+    );
+}
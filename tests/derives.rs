@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq, New: Clone)]
+#[intermediate_derive(Captured: Default + ::std::hash::Hash, New: PartialEq)]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+fn hash<T: ::std::hash::Hash>(_: &T) {}
+
+#[test]
+fn keyed_derives_only_apply_to_their_intermediate() {
+    let new = NewRust {
+        life_cycle_stage: 1,
+    };
+    assert_eq!(new.clone(), new);
+
+    let captured = CapturedRust::default();
+    hash(&captured);
+    assert_eq!(
+        captured,
+        CapturedRust {
+            mycologist_id: 0,
+            life_cycle_stage: 0,
+        }
+    );
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_attr(repr(align(16)))]
+#[intermediate_attr(Captured, repr(align(32)))]
+pub struct Specimen {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+#[test]
+fn struct_attributes_apply_to_their_intermediates() {
+    assert_eq!(::std::mem::align_of::<NewSpecimen>(), 16);
+    assert_eq!(::std::mem::align_of::<CapturedSpecimen>(), 32);
+    assert_eq!(::std::mem::align_of::<Specimen>(), 4);
+}
@@ -1,4 +1,5 @@
-#![cfg_attr(feature = "cargo-clippy", allow(redundant_field_names))]
+#![allow(clippy::redundant_field_names)]
+#![allow(non_local_definitions)]
 
 #[macro_use]
 extern crate diesel;
@@ -32,6 +33,21 @@ table! {
     }
 }
 
+table! {
+    captured_rusts {
+        id -> Integer,
+        mycologist_id -> Integer,
+        life_cycle_stage -> Integer,
+    }
+}
+
+table! {
+    fungi {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
 table! {
     mikes {
         id -> Integer,
@@ -40,7 +56,7 @@ table! {
 }
 
 mod items {
-    use super::{mikes, mycologists, rusts};
+    use super::{captured_rusts, fungi, mikes, mycologists, petri_dishes, rusts};
 
     #[derive(DieselIntermediate, Debug, Clone, PartialEq, Identifiable, Insertable, Queryable)]
     #[intermediate_derive(Debug, PartialEq, Insertable)]
@@ -81,6 +97,88 @@ mod items {
         pub mycologist_id: i32,
         pub life_cycle_stage: i32,
     }
+
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq, Identifiable, Queryable)]
+    #[intermediate_derive(Debug, PartialEq, Insertable)]
+    #[table_name = "rusts"]
+    #[intermediate_table_name(Captured = "captured_rusts")]
+    pub struct Specimen {
+        #[intermediate_exclude]
+        pub id: i32,
+        #[intermediate_exclude(Captured)]
+        pub mycologist_id: i32,
+        pub life_cycle_stage: i32,
+    }
+
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq, Insertable, Queryable)]
+    #[intermediate_derive(Debug, PartialEq, Insertable, Queryable)]
+    #[table_name = "petri_dishes"]
+    pub struct PetriDish {
+        #[intermediate_exclude]
+        pub id: i32,
+        pub size: i32,
+        #[intermediate_exclude(Captured)]
+        pub mycologist_id: i32,
+    }
+
+    // only the intermediate that gets inserted needs to know the column name
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq, Queryable)]
+    #[intermediate_derive(Debug, PartialEq, Insertable)]
+    #[intermediate_table_name = "mycologists"]
+    pub struct Counter {
+        #[intermediate_exclude]
+        pub id: i32,
+        #[intermediate_field_attr(New, column_name = "rust_count")]
+        pub count: i32,
+    }
+
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq, Insertable, Queryable)]
+    #[intermediate_derive(Debug, PartialEq, Insertable)]
+    #[intermediate_ref]
+    #[intermediate_changeset(Patch)]
+    #[intermediate_names(Patch = "FungusPatch")]
+    #[table_name = "fungi"]
+    pub struct Fungus {
+        #[intermediate_exclude]
+        pub id: i32,
+        pub name: String,
+    }
+
+    // `PatchStrain` doesn't use `Id`, so only its `apply` takes it
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq)]
+    #[intermediate_derive(Debug, PartialEq)]
+    #[intermediate_changeset(Patch)]
+    #[intermediate_table_name = "fungi"]
+    pub struct Strain<Id> {
+        #[intermediate_exclude]
+        pub id: Id,
+        pub name: String,
+    }
+
+    // only `NewCulture` is `Insertable` by default, `StagedCulture` doesn't
+    // derive anything that reads the table name
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq)]
+    #[intermediate_derive(Debug, PartialEq)]
+    #[intermediate_table_name = "rusts"]
+    pub struct Culture {
+        #[intermediate_exclude]
+        pub id: i32,
+        pub mycologist_id: i32,
+        #[intermediate_exclude(Staged)]
+        pub life_cycle_stage: i32,
+    }
+
+    // `NewNote` can't be `Insertable`, `text` isn't a column
+    #[derive(DieselIntermediate, Debug, Clone, PartialEq, Identifiable)]
+    #[intermediate_derive(Debug, PartialEq, Identifiable)]
+    #[intermediate_insertable()]
+    #[table_name = "mycologists"]
+    pub struct Note {
+        pub id: i32,
+        pub text: String,
+        #[intermediate_exclude]
+        pub rust_count: i32,
+    }
 }
 
 use items::*;
@@ -116,6 +214,36 @@ fn setup() -> SqliteConnection {
         )",
     );
     setup.execute(&conn).expect("Can't create table: mikes");
+    let setup = sql::<diesel::sql_types::Bool>(
+        "
+        CREATE TABLE captured_rusts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            mycologist_id INTEGER NOT NULL,
+            life_cycle_stage INTEGER NOT NULL
+        )",
+    );
+    setup
+        .execute(&conn)
+        .expect("Can't create table: captured_rusts");
+    let setup = sql::<diesel::sql_types::Bool>(
+        "
+        CREATE TABLE petri_dishes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            mycologist_id INTEGER NOT NULL,
+            size INTEGER NOT NULL
+        )",
+    );
+    setup
+        .execute(&conn)
+        .expect("Can't create table: petri_dishes");
+    let setup = sql::<diesel::sql_types::Bool>(
+        "
+        CREATE TABLE fungi (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL
+        )",
+    );
+    setup.execute(&conn).expect("Can't create table: fungi");
     conn
 }
 
@@ -216,3 +344,160 @@ fn can_insert_into_intermediate_table() {
         .execute(&conn)
         .expect("Couldn't insert mike into mycologists table");
 }
+
+#[test]
+fn can_insert_into_per_intermediate_table() {
+    let conn = setup();
+    let specimen = CapturedSpecimen {
+        mycologist_id: 4,
+        life_cycle_stage: 2,
+    };
+
+    diesel::insert_into(captured_rusts::table)
+        .values(&specimen)
+        .execute(&conn)
+        .expect("Couldn't insert specimen into captured_rusts");
+
+    let found = captured_rusts::table.load::<Specimen>(&conn).unwrap();
+    assert_eq!(found, vec![Specimen::from_captured_specimen(1, specimen)]);
+}
+
+#[test]
+fn intermediates_keep_field_order_for_queryable() {
+    let conn = setup();
+    let dish = CapturedPetriDish {
+        size: 10,
+        mycologist_id: 3,
+    };
+
+    diesel::insert_into(petri_dishes::table)
+        .values(&dish)
+        .execute(&conn)
+        .expect("Couldn't insert dish into petri_dishes");
+
+    let found = petri_dishes::table
+        .select((petri_dishes::size, petri_dishes::mycologist_id))
+        .load::<CapturedPetriDish>(&conn)
+        .unwrap();
+
+    assert_eq!(found, vec![dish]);
+}
+
+#[test]
+fn can_batch_insert_borrowed_intermediates() {
+    let conn = setup();
+    let fungi = [
+        Fungus {
+            id: 7,
+            name: "rust".to_string(),
+        },
+        Fungus {
+            id: 8,
+            name: "smut".to_string(),
+        },
+    ];
+
+    let borrowed = fungi.iter().map(NewFungusRef::from).collect::<Vec<_>>();
+    diesel::insert_into(fungi::table)
+        .values(&borrowed)
+        .execute(&conn)
+        .expect("Couldn't insert borrowed fungi");
+
+    let found = fungi::table.load::<Fungus>(&conn).unwrap();
+    let names = found
+        .iter()
+        .map(|f| f.to_new_fungus_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(names, borrowed);
+    assert_eq!(found[0].id, 1);
+}
+
+#[test]
+fn can_update_with_changeset() {
+    let conn = setup();
+    diesel::insert_into(fungi::table)
+        .values(&NewFungus {
+            name: "rust".to_string(),
+        })
+        .execute(&conn)
+        .expect("Couldn't insert fungus");
+    let mut fungus = fungi::table.first::<Fungus>(&conn).unwrap();
+
+    let patch = FungusPatch {
+        name: Some("smut".to_string()),
+    };
+    diesel::update(fungi::table.find(fungus.id))
+        .set(&patch)
+        .execute(&conn)
+        .expect("Couldn't update fungus");
+    patch.apply(&mut fungus);
+    assert_eq!(fungus.name, "smut");
+    assert_eq!(fungi::table.first::<Fungus>(&conn).unwrap(), fungus);
+
+    FungusPatch { name: None }.apply(&mut fungus);
+    assert_eq!(fungus.name, "smut");
+}
+
+#[test]
+fn generic_changesets_apply_to_the_full_struct() {
+    let mut strain = Strain {
+        id: "puccinia",
+        name: "rust".to_string(),
+    };
+    PatchStrain {
+        name: Some("smut".to_string()),
+    }
+    .apply(&mut strain);
+    assert_eq!(
+        strain,
+        Strain {
+            id: "puccinia",
+            name: "smut".to_string(),
+        }
+    );
+}
+
+#[test]
+fn field_attributes_go_on_one_intermediate() {
+    let conn = setup();
+    diesel::insert_into(mycologists::table)
+        .values(&NewCounter { count: 4 })
+        .execute(&conn)
+        .expect("Couldn't insert counter");
+
+    let found = mycologists::table.load::<Counter>(&conn).unwrap();
+    assert_eq!(found, vec![Counter { id: 1, count: 4 }]);
+}
+
+#[test]
+fn insertable_can_be_turned_off() {
+    let note = NewNote {
+        id: 3,
+        text: "rusty".to_string(),
+    };
+    assert_eq!(*note.id(), 3);
+}
+
+#[test]
+fn insert_shapes_are_insertable_by_default() {
+    let conn = setup();
+    diesel::insert_into(rusts::table)
+        .values(&NewCulture { mycologist_id: 4 })
+        .execute(&conn)
+        .expect("Couldn't insert struct into rusts");
+
+    let mycologist_id = rusts::table
+        .select(rusts::mycologist_id)
+        .first::<i32>(&conn)
+        .unwrap();
+    assert_eq!(mycologist_id, 4);
+
+    let staged = StagedCulture::from_new_culture(2, NewCulture { mycologist_id });
+    assert_eq!(
+        staged,
+        StagedCulture {
+            mycologist_id: 4,
+            life_cycle_stage: 2,
+        }
+    );
+}
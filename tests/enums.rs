@@ -0,0 +1,111 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A mycologist found some rust
+    Found {
+        #[intermediate_exclude]
+        id: i32,
+        #[intermediate_exclude(Captured)]
+        mycologist_id: i32,
+        rust_count: i32,
+    },
+    Renamed {
+        #[intermediate_exclude]
+        id: i32,
+        name: String,
+    },
+    Moved(String),
+    Reset,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub enum Sample {
+    Spore(#[intermediate_exclude] i32, String),
+    Culture(#[intermediate_exclude] String, u8),
+}
+
+#[test]
+fn variants_lose_their_excluded_fields() {
+    let new = NewEvent::Found { rust_count: 3 };
+    let captured = CapturedEvent::from_new_event_found(2, new.clone()).unwrap();
+    assert_eq!(
+        captured,
+        CapturedEvent::Found {
+            mycologist_id: 2,
+            rust_count: 3,
+        }
+    );
+
+    assert_eq!(
+        Event::from_captured_event_found(1, captured).unwrap(),
+        Event::Found {
+            id: 1,
+            mycologist_id: 2,
+            rust_count: 3,
+        }
+    );
+    assert_eq!(
+        Event::from_new_event_found(1, 2, new).unwrap(),
+        Event::Found {
+            id: 1,
+            mycologist_id: 2,
+            rust_count: 3,
+        }
+    );
+}
+
+#[test]
+fn constructors_only_take_the_fields_of_their_variant() {
+    assert_eq!(
+        Event::from_new_event_renamed(
+            1,
+            NewEvent::Renamed {
+                name: "Mike".into(),
+            }
+        ),
+        Ok(Event::Renamed {
+            id: 1,
+            name: "Mike".into(),
+        })
+    );
+    assert_eq!(
+        Event::from_captured_event_moved(CapturedEvent::Moved("Forest".into())),
+        Ok(Event::Moved("Forest".into()))
+    );
+    assert_eq!(
+        Event::from_captured_event_reset(CapturedEvent::Reset),
+        Ok(Event::Reset)
+    );
+    assert_eq!(
+        CapturedEvent::from_new_event_reset(NewEvent::Reset),
+        Ok(CapturedEvent::Reset)
+    );
+}
+
+#[test]
+fn other_variants_are_given_back() {
+    assert_eq!(
+        Event::from_new_event_renamed(1, NewEvent::Reset),
+        Err(NewEvent::Reset)
+    );
+    assert_eq!(
+        CapturedEvent::from_new_event_found(2, NewEvent::Moved("Forest".into())),
+        Err(NewEvent::Moved("Forest".into()))
+    );
+}
+
+#[test]
+fn tuple_variants_take_their_own_positional_fields() {
+    assert_eq!(
+        Sample::from_new_sample_spore(1, NewSample::Spore("teliospore".into())),
+        Ok(Sample::Spore(1, "teliospore".into()))
+    );
+    assert_eq!(
+        Sample::from_new_sample_culture("agar".into(), NewSample::Culture(3)),
+        Ok(Sample::Culture("agar".into(), 3))
+    );
+}
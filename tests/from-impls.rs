@@ -0,0 +1,63 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Tagged<'a, Id: Copy> {
+    #[intermediate_exclude]
+    pub id: Id,
+    pub tag: &'a str,
+}
+
+#[derive(Debug)]
+pub struct NoId;
+
+fn assign_id<T: From<(i32, NewRust)>>(id: Option<i32>, new: NewRust) -> Result<T, NoId> {
+    Ok((id.ok_or(NoId)?, new).into())
+}
+
+#[test]
+fn tuples_of_missing_fields_convert() {
+    let new = NewRust {
+        life_cycle_stage: 1,
+    };
+    let captured: CapturedRust = (2, new.clone()).into();
+    assert_eq!(captured, CapturedRust::from_new_rust(2, new.clone()));
+    assert_eq!(
+        Rust::from((3, captured.clone())),
+        Rust::from_captured_rust(3, captured)
+    );
+    assert_eq!(
+        Rust::from((3, 2, new.clone())),
+        Rust::from_new_rust(3, 2, new)
+    );
+}
+
+#[test]
+fn generic_code_can_complete_intermediates() {
+    let new = NewRust {
+        life_cycle_stage: 1,
+    };
+    let rust: Rust = match assign_id::<CapturedRust>(Some(2), new.clone()) {
+        Ok(captured) => (1, captured).into(),
+        Err(e) => panic!("{:?}", e),
+    };
+    assert_eq!(rust.mycologist_id, 2);
+    assert!(assign_id::<CapturedRust>(None, new).is_err());
+}
+
+#[test]
+fn generic_conversions() {
+    let tagged: Tagged<u8> = (7u8, NewTagged { tag: "t" }).into();
+    assert_eq!(tagged, Tagged { id: 7, tag: "t" });
+}
@@ -0,0 +1,119 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Post<'a> {
+    #[intermediate_exclude]
+    pub id: i32,
+    pub title: &'a str,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Tagged<'a, Id: Copy, T = String>
+where
+    T: Debug,
+{
+    #[intermediate_exclude]
+    pub id: Id,
+    #[intermediate_exclude(Captured)]
+    pub author: &'a str,
+    pub tag: T,
+}
+
+// no intermediate has any use for `K` or `'k`
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub struct Keyed<'k, K: Debug + 'k, const N: usize> {
+    #[intermediate_exclude]
+    pub key: &'k K,
+    pub values: [u8; N],
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Dropped<A, B, C> {
+    #[intermediate_exclude(Left)]
+    pub left: A,
+    #[intermediate_exclude(Right)]
+    pub right: B,
+    pub common: PhantomData<C>,
+}
+
+// `U` only shows up in the bounds on `T`, which the intermediates don't need
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Converted<T: Into<U>, U, V>
+where
+    V: From<U>,
+{
+    #[intermediate_exclude]
+    pub u: U,
+    #[intermediate_exclude(Captured)]
+    pub v: V,
+    pub t: T,
+}
+
+#[test]
+fn borrowed_structs() {
+    let new = NewPost { title: "hi" };
+    assert_eq!(
+        Post::from_new_post(1, new.clone()),
+        Post { id: 1, title: "hi" }
+    );
+}
+
+#[test]
+fn unused_params_are_pruned() {
+    let new: NewTagged<u8> = NewTagged { tag: 1 };
+    let captured: CapturedTagged<u8> = CapturedTagged::from_new_tagged("me", new);
+    assert_eq!(
+        Tagged::from_captured_tagged(5u64, captured),
+        Tagged {
+            id: 5u64,
+            author: "me",
+            tag: 1u8,
+        }
+    );
+
+    let key = "k".to_string();
+    let new: NewKeyed<3> = NewKeyed { values: [1, 2, 3] };
+    assert_eq!(
+        Keyed::from_new_keyed(&key, new),
+        Keyed {
+            key: &key,
+            values: [1, 2, 3],
+        }
+    );
+}
+
+#[test]
+fn dropped_fields_bring_their_params() {
+    let left: LeftDropped<i32, ()> = LeftDropped {
+        left: 1,
+        common: PhantomData,
+    };
+    let right: RightDropped<&str, ()> = RightDropped::from_left_dropped("right", left);
+    assert_eq!(right.right, "right");
+}
+
+#[test]
+fn bounds_on_pruned_params_are_dropped() {
+    let new: NewConverted<u8> = NewConverted { t: 1 };
+    let captured: CapturedConverted<u8, u64> = CapturedConverted::from_new_converted(2, new);
+    let converted: Converted<u8, u32, u64> = Converted::from_captured_converted(3, captured);
+    assert_eq!(
+        converted,
+        Converted {
+            u: 3u32,
+            v: 2u64,
+            t: 1u8,
+        }
+    );
+    assert_eq!(converted.to_new_converted(), NewConverted { t: 1 });
+    assert_eq!(NewConverted::from(converted), NewConverted { t: 1 });
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "test-postgres")]
+#![allow(non_local_definitions)]
+
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+use diesel::pg::PgConnection;
+use diesel::QueryResult;
+
+table! {
+    rusts {
+        id -> Integer,
+        mycologist_id -> Integer,
+        life_cycle_stage -> Integer,
+    }
+}
+
+#[derive(DieselIntermediate, Debug, Clone, PartialEq, Queryable)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediate_insertable(New, Captured)]
+#[intermediate_insert(backend = "pg")]
+#[intermediate_table_name = "rusts"]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+// there is no database to run these against, they only check that the
+// generated `insert`s type-check
+#[allow(dead_code)]
+fn insert_new(conn: &PgConnection) -> QueryResult<Rust> {
+    NewRust {
+        life_cycle_stage: 1,
+    }
+    .insert(conn)
+}
+
+#[allow(dead_code)]
+fn insert_captured(conn: &PgConnection) -> QueryResult<Rust> {
+    CapturedRust {
+        mycologist_id: 4,
+        life_cycle_stage: 2,
+    }
+    .insert(conn)
+}
@@ -0,0 +1,196 @@
+#![allow(non_local_definitions)]
+
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+table! {
+    rusts {
+        id -> Integer,
+        mycologist_id -> Integer,
+        life_cycle_stage -> Integer,
+    }
+}
+
+table! {
+    labels (code) {
+        code -> Text,
+        text -> Text,
+    }
+}
+
+table! {
+    tags (name) {
+        name -> Text,
+        uses -> Integer,
+    }
+}
+
+#[derive(DieselIntermediate, Debug, Clone, PartialEq, Queryable)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediate_insertable(New, Captured)]
+#[intermediate_insert(backend = "sqlite")]
+#[intermediate_table_name = "rusts"]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+// the database picks the primary key, but it isn't the rowid
+#[derive(DieselIntermediate, Debug, PartialEq, Queryable)]
+#[intermediate_insert(backend = "sqlite")]
+#[intermediate_table_name = "labels"]
+pub struct Label {
+    #[intermediate_exclude]
+    pub code: String,
+    pub text: String,
+}
+
+// the primary key is in `NewTag`, and there is no rowid to find it by
+#[derive(DieselIntermediate, Debug, PartialEq, Identifiable, Queryable)]
+#[intermediate_insert(backend = "sqlite")]
+#[table_name = "tags"]
+#[primary_key(name)]
+pub struct Tag {
+    pub name: String,
+    #[intermediate_exclude]
+    pub uses: i32,
+}
+
+// `Id` only has to be `Queryable` where `insert` is called
+#[derive(DieselIntermediate, Debug, PartialEq, Queryable)]
+#[intermediate_insert(backend = "sqlite")]
+#[intermediate_table_name = "labels"]
+pub struct Sticker<Id> {
+    #[intermediate_exclude]
+    pub code: Id,
+    pub text: String,
+}
+
+fn setup() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+    sql::<diesel::sql_types::Bool>(
+        "
+        CREATE TABLE rusts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            mycologist_id INTEGER NOT NULL DEFAULT 0,
+            life_cycle_stage INTEGER NOT NULL
+        )",
+    )
+    .execute(&conn)
+    .expect("Can't create table: rusts");
+    sql::<diesel::sql_types::Bool>(
+        "
+        CREATE TABLE labels (
+            code TEXT PRIMARY KEY NOT NULL DEFAULT 'unlabelled',
+            text TEXT NOT NULL
+        )",
+    )
+    .execute(&conn)
+    .expect("Can't create table: labels");
+    sql::<diesel::sql_types::Bool>(
+        "
+        CREATE TABLE tags (
+            name TEXT PRIMARY KEY NOT NULL,
+            uses INTEGER NOT NULL DEFAULT 0
+        ) WITHOUT ROWID",
+    )
+    .execute(&conn)
+    .expect("Can't create table: tags");
+    conn
+}
+
+#[test]
+fn insert_returns_the_full_struct() {
+    let conn = setup();
+    let first = NewRust {
+        life_cycle_stage: 1,
+    }
+    .insert(&conn)
+    .expect("Couldn't insert rust");
+    let second = CapturedRust {
+        mycologist_id: 4,
+        life_cycle_stage: 2,
+    }
+    .insert(&conn)
+    .expect("Couldn't insert rust");
+
+    assert_eq!(
+        first,
+        Rust {
+            id: 1,
+            mycologist_id: 0,
+            life_cycle_stage: 1,
+        }
+    );
+    assert_eq!(
+        second,
+        Rust {
+            id: 2,
+            mycologist_id: 4,
+            life_cycle_stage: 2,
+        }
+    );
+    assert_eq!(
+        rusts::table.load::<Rust>(&conn).unwrap(),
+        vec![first, second]
+    );
+}
+
+#[test]
+fn insert_finds_rows_without_an_integer_primary_key() {
+    let conn = setup();
+    let label = NewLabel {
+        text: "smut".to_string(),
+    }
+    .insert(&conn)
+    .expect("Couldn't insert label");
+    assert_eq!(
+        label,
+        Label {
+            code: "unlabelled".to_string(),
+            text: "smut".to_string(),
+        }
+    );
+}
+
+#[test]
+fn insert_finds_rows_by_a_primary_key_it_has() {
+    let conn = setup();
+    NewTag {
+        name: "rust".to_string(),
+    }
+    .insert(&conn)
+    .expect("Couldn't insert tag");
+    let tag = NewTag {
+        name: "smut".to_string(),
+    }
+    .insert(&conn)
+    .expect("Couldn't insert tag");
+    assert_eq!(
+        tag,
+        Tag {
+            name: "smut".to_string(),
+            uses: 0,
+        }
+    );
+}
+
+#[test]
+fn generic_structs_can_be_inserted() {
+    let conn = setup();
+    let sticker: Sticker<String> = NewSticker {
+        text: "rust".to_string(),
+    }
+    .insert(&conn)
+    .expect("Couldn't insert sticker");
+    assert_eq!(sticker.code, "unlabelled");
+}
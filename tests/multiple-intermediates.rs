@@ -0,0 +1,101 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Big {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude]
+    pub meta: i32,
+    #[intermediate_exclude(Outer)]
+    pub outer: i32,
+    #[intermediate_exclude(Outer, Inner)]
+    pub outer_inner: i32,
+    #[intermediate_exclude(Inner)]
+    pub inner: i32,
+
+    pub common: i32,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Mycologist {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Reviewed)]
+    pub reviewer: String,
+    #[intermediate_exclude(Captured, Reviewed)]
+    pub location: String,
+    #[intermediate_exclude(Draft, Captured, Reviewed)]
+    pub name: String,
+    pub rust_count: i32,
+}
+
+#[test]
+fn overlapping_intermediates_have_their_fields() {
+    let outer = OuterBig {
+        outer: 1,
+        outer_inner: 2,
+        common: 3,
+    };
+    let inner = InnerBig {
+        outer_inner: 2,
+        inner: 4,
+        common: 3,
+    };
+
+    assert_eq!(OuterBig::from_inner_big(1, inner.clone()), outer);
+    assert_eq!(InnerBig::from_outer_big(4, outer.clone()), inner);
+    assert_eq!(
+        OuterBig::from_new_big(1, 2, NewBig { common: 3 }),
+        outer.clone()
+    );
+    assert_eq!(InnerBig::from_new_big(2, 4, NewBig { common: 3 }), inner);
+
+    let expected = Big {
+        id: 5,
+        meta: 6,
+        outer: 1,
+        outer_inner: 2,
+        inner: 4,
+        common: 3,
+    };
+    assert_eq!(Big::from_outer_big(5, 6, 4, outer), expected);
+    assert_eq!(Big::from_inner_big(5, 6, 1, inner), expected);
+    assert_eq!(
+        Big::from_new_big(5, 6, 1, 2, 4, NewBig { common: 3 }),
+        expected
+    );
+}
+
+#[test]
+fn stages_build_on_each_other() {
+    let new = NewMycologist { rust_count: 0 };
+    let draft = DraftMycologist::from_new_mycologist("Mike".into(), new.clone());
+    let captured = CapturedMycologist::from_draft_mycologist("Forest".into(), draft.clone());
+    let reviewed = ReviewedMycologist::from_captured_mycologist("Anne".into(), captured.clone());
+
+    assert_eq!(
+        reviewed,
+        ReviewedMycologist::from_new_mycologist("Anne".into(), "Forest".into(), "Mike".into(), new)
+    );
+    assert_eq!(
+        ReviewedMycologist::from_draft_mycologist("Anne".into(), "Forest".into(), draft),
+        reviewed
+    );
+    assert_eq!(
+        Mycologist::from_reviewed_mycologist(1, reviewed),
+        Mycologist {
+            id: 1,
+            reviewer: "Anne".into(),
+            location: "Forest".into(),
+            name: "Mike".into(),
+            rust_count: 0,
+        }
+    );
+    assert_eq!(
+        Mycologist::from_captured_mycologist(1, "Anne".into(), captured).reviewer,
+        "Anne"
+    );
+}
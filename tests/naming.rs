@@ -0,0 +1,71 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediate_names(suffix)]
+pub struct Mycologist {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Draft)]
+    pub name: String,
+    pub rust_count: i32,
+}
+
+// a hand-written type that would collide with the default `NewRust`
+pub struct NewRust;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediate_names(New = "RustInsert", Captured = "CapturedRust")]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+#[test]
+fn suffix_names() {
+    let new = MycologistNew { rust_count: 1 };
+    let draft = MycologistDraft::from_mycologist_new("Mike".into(), new.clone());
+    assert_eq!(
+        Mycologist::from_mycologist_draft(1, draft),
+        Mycologist::from_mycologist_new(1, "Mike".into(), new)
+    );
+}
+
+#[test]
+fn explicit_names() {
+    let _ = NewRust;
+    let new = RustInsert {
+        life_cycle_stage: 2,
+    };
+    let captured = CapturedRust::from_rust_insert(3, new.clone());
+    assert_eq!(
+        Rust::from_captured_rust(1, captured),
+        Rust::from_rust_insert(1, 3, new)
+    );
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediate_constructor(New = "persisted", Captured = "from_capture")]
+pub struct Spore {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub size: i32,
+}
+
+#[test]
+fn renamed_constructors() {
+    let new = NewSpore { size: 2 };
+    let captured = CapturedSpore::persisted(3, new.clone());
+    assert_eq!(
+        Spore::from_capture(1, captured),
+        Spore::persisted(1, 3, new)
+    );
+}
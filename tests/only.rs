@@ -0,0 +1,92 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, Clone, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Rust {
+    #[intermediate_exclude]
+    #[intermediate_only(Summary, Label)]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    #[intermediate_only(Summary)]
+    pub name: String,
+    pub life_cycle_stage: i32,
+    pub spore_count: i32,
+}
+
+#[derive(DieselIntermediate, Debug, Clone, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Found {
+        #[intermediate_only(Summary)]
+        id: i32,
+        rust_count: i32,
+    },
+    Reset,
+}
+
+#[test]
+fn only_intermediates_have_just_their_fields() {
+    let rust = Rust {
+        id: 1,
+        mycologist_id: 2,
+        name: "puccinia".to_string(),
+        life_cycle_stage: 3,
+        spore_count: 4,
+    };
+    assert_eq!(
+        rust.to_summary_rust(),
+        SummaryRust {
+            id: 1,
+            name: "puccinia".to_string(),
+        }
+    );
+    assert_eq!(LabelRust::from(rust.clone()), LabelRust { id: 1 });
+    assert_eq!(rust.to_summary_rust().to_label_rust(), LabelRust { id: 1 });
+}
+
+#[test]
+fn only_intermediates_get_constructors() {
+    let summary = SummaryRust {
+        id: 1,
+        name: "puccinia".to_string(),
+    };
+    assert_eq!(
+        Rust::from_summary_rust(2, 3, 4, summary.clone()),
+        Rust {
+            id: 1,
+            mycologist_id: 2,
+            name: "puccinia".to_string(),
+            life_cycle_stage: 3,
+            spore_count: 4,
+        }
+    );
+
+    let new = NewRust {
+        name: "smut".to_string(),
+        life_cycle_stage: 3,
+        spore_count: 4,
+    };
+    assert_eq!(
+        SummaryRust::from_new_rust(5, new),
+        SummaryRust {
+            id: 5,
+            name: "smut".to_string(),
+        }
+    );
+}
+
+#[test]
+fn only_intermediates_work_for_enums() {
+    let found = Event::Found {
+        id: 1,
+        rust_count: 2,
+    };
+    assert_eq!(found.to_summary_event(), SummaryEvent::Found { id: 1 });
+    assert_eq!(Event::Reset.to_summary_event(), SummaryEvent::Reset {});
+    assert_eq!(
+        Event::from_summary_event_found(2, SummaryEvent::Found { id: 1 }),
+        Ok(found)
+    );
+}
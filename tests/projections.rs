@@ -0,0 +1,107 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, Clone, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub life_cycle_stage: i32,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub struct Sample<'a, T>(#[intermediate_exclude] pub i32, pub &'a str, pub T);
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub enum Event {
+    Found {
+        #[intermediate_exclude]
+        id: i32,
+        rust_count: i32,
+    },
+    Reset,
+}
+
+/// Not `Clone`, so only the consuming projection can be used
+#[derive(Debug, PartialEq)]
+pub struct Handle(i32);
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub struct Opened {
+    #[intermediate_exclude]
+    pub id: i32,
+    pub handle: Handle,
+}
+
+#[test]
+fn full_structs_project_down() {
+    let rust = Rust {
+        id: 1,
+        mycologist_id: 2,
+        life_cycle_stage: 3,
+    };
+    assert_eq!(
+        rust.to_new_rust(),
+        NewRust {
+            life_cycle_stage: 3
+        }
+    );
+    assert_eq!(
+        rust.to_captured_rust(),
+        CapturedRust {
+            mycologist_id: 2,
+            life_cycle_stage: 3,
+        }
+    );
+
+    let captured: CapturedRust = rust.clone().into();
+    assert_eq!(Rust::from_captured_rust(1, captured), rust);
+    assert_eq!(NewRust::from(rust.clone()), rust.to_new_rust());
+}
+
+#[test]
+fn tuple_generic_and_enum_projections() {
+    let sample = Sample(1, "spore", vec![2]);
+    assert_eq!(sample.to_new_sample(), NewSample("spore", vec![2]));
+    assert_eq!(NewSample::from(sample), NewSample("spore", vec![2]));
+
+    let found = Event::Found {
+        id: 1,
+        rust_count: 3,
+    };
+    assert_eq!(found.to_new_event(), NewEvent::Found { rust_count: 3 });
+    assert_eq!(NewEvent::from(Event::Reset), NewEvent::Reset);
+}
+
+#[test]
+fn fields_that_are_not_clone_can_still_be_moved() {
+    let opened = Opened {
+        id: 1,
+        handle: Handle(2),
+    };
+    assert_eq!(NewOpened::from(opened), NewOpened { handle: Handle(2) });
+}
+
+fn stage_of(new: NewRust) -> i32 {
+    new.life_cycle_stage
+}
+
+#[test]
+fn richer_intermediates_project_to_narrower_ones() {
+    let captured = CapturedRust {
+        mycologist_id: 2,
+        life_cycle_stage: 3,
+    };
+    assert_eq!(
+        captured.to_new_rust(),
+        NewRust {
+            life_cycle_stage: 3
+        }
+    );
+    assert_eq!(stage_of(captured.into()), 3);
+}
@@ -0,0 +1,68 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediate_ref]
+pub struct Rust {
+    #[intermediate_exclude]
+    pub id: i32,
+    #[intermediate_exclude(Captured)]
+    pub mycologist_id: i32,
+    pub name: String,
+}
+
+// `'a` is taken, so the borrowed intermediate picks another lifetime
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+#[intermediate_ref]
+pub struct Post<'a, T> {
+    #[intermediate_exclude]
+    pub id: T,
+    pub title: &'a str,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+#[intermediate_ref]
+pub enum Event {
+    Found {
+        #[intermediate_exclude]
+        id: i32,
+        name: String,
+    },
+    Moved(String),
+}
+
+#[test]
+fn borrowed_intermediates_point_at_the_original() {
+    let rust = Rust {
+        id: 1,
+        mycologist_id: 2,
+        name: "puccinia".to_string(),
+    };
+    let borrowed = NewRustRef::from(&rust);
+    assert!(std::ptr::eq(borrowed.name, &rust.name));
+
+    let new = NewRust {
+        name: "puccinia".to_string(),
+    };
+    assert_eq!(new.to_new_rust_ref(), rust.to_new_rust_ref());
+}
+
+#[test]
+fn borrowed_generic_and_enum_intermediates() {
+    let title = String::from("hello");
+    let post = Post {
+        id: 1u64,
+        title: &title,
+    };
+    let borrowed: NewPostRef = (&post).into();
+    assert_eq!(*borrowed.title, "hello");
+
+    let moved = Event::Moved("there".to_string());
+    assert_eq!(
+        moved.to_new_event_ref(),
+        NewEventRef::Moved(&"there".to_string())
+    );
+}
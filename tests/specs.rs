@@ -0,0 +1,60 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+#[intermediates(
+    New(exclude(id, mycologist_id, created_at)),
+    Captured(exclude(id, created_at))
+)]
+#[intermediates(Summary(include(id, name)))]
+pub struct Rust {
+    pub id: i32,
+    pub mycologist_id: i32,
+    pub name: String,
+    pub created_at: u64,
+}
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+#[intermediates(New(exclude(0)))]
+pub struct Sample(pub i32, pub String);
+
+#[test]
+fn specs_group_fields_like_field_attributes() {
+    let new = NewRust {
+        name: "puccinia".to_string(),
+    };
+    let captured = CapturedRust::from_new_rust(2, new.clone());
+    assert_eq!(
+        captured,
+        CapturedRust {
+            mycologist_id: 2,
+            name: "puccinia".to_string(),
+        }
+    );
+    let rust = Rust::from_captured_rust(1, 3, captured);
+    assert_eq!(
+        rust,
+        Rust {
+            id: 1,
+            mycologist_id: 2,
+            name: "puccinia".to_string(),
+            created_at: 3,
+        }
+    );
+    assert_eq!(Rust::from_new_rust(1, 2, 3, new), rust);
+    assert_eq!(
+        rust.to_summary_rust(),
+        SummaryRust {
+            id: 1,
+            name: "puccinia".to_string(),
+        }
+    );
+}
+
+#[test]
+fn specs_name_tuple_fields_by_position() {
+    let sample = Sample::from_new_sample(1, NewSample("rust".to_string()));
+    assert_eq!(sample, Sample(1, "rust".to_string()));
+}
@@ -0,0 +1,51 @@
+// this is a compile-pass test for input that syn 0.11 couldn't handle
+#![allow(dead_code)]
+
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+mod visibility {
+    #[derive(DieselIntermediate)]
+    #[intermediate_derive(Debug, Clone)]
+    pub(crate) struct Restricted {
+        #[intermediate_exclude]
+        pub(crate) id: i32,
+        pub(super) other: i32,
+    }
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_derive(Debug, ::std::clone::Clone)]
+struct r#Match {
+    #[intermediate_exclude]
+    r#ref: i32,
+    #[intermediate_exclude(Scheduled)]
+    r#type: i32,
+    r#loop: bool,
+}
+
+macro_rules! intermediate {
+    ($name:ident, $field:ident) => {
+        #[derive(DieselIntermediate)]
+        #[intermediate_derive(Debug)]
+        struct $name {
+            #[intermediate_exclude]
+            id: i32,
+            $field: i32,
+        }
+    };
+}
+
+intermediate!(Generated, count);
+
+fn builds() {
+    let restricted = visibility::NewRestricted { other: 1 };
+    let _ = visibility::Restricted::from_new_restricted(1, restricted.clone());
+    assert_eq!(restricted.other, 1);
+
+    let new = NewMatch { r#loop: true };
+    let scheduled = ScheduledMatch::from_new_match(2, new);
+    let _ = r#Match::from_scheduled_match(1, scheduled);
+
+    let _ = Generated::from_new_generated(1, NewGenerated { count: 2 });
+}
@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RustId(i32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MycologistId(i32);
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, Clone, PartialEq)]
+pub struct Rust(
+    #[intermediate_exclude] pub RustId,
+    /// the mycologist that found it
+    #[intermediate_exclude(Captured)]
+    pub MycologistId,
+    pub i32,
+);
+
+// the common field comes first, so the prefixed one can't just be put in
+// front of the others
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub struct Specimen(
+    #[intermediate_exclude] pub RustId,
+    pub i32,
+    #[intermediate_exclude(Captured)] pub MycologistId,
+);
+
+#[derive(DieselIntermediate, Debug, PartialEq)]
+#[intermediate_derive(Debug, PartialEq)]
+pub struct Spore;
+
+#[test]
+fn tuple_intermediates_are_positional() {
+    let new = NewRust(3);
+    assert_eq!(new.0, 3);
+
+    let captured = CapturedRust::from_new_rust(MycologistId(2), new.clone());
+    assert_eq!(captured, CapturedRust(MycologistId(2), 3));
+
+    assert_eq!(
+        Rust::from_captured_rust(RustId(1), captured),
+        Rust(RustId(1), MycologistId(2), 3)
+    );
+    assert_eq!(
+        Rust::from_new_rust(RustId(1), MycologistId(2), new),
+        Rust(RustId(1), MycologistId(2), 3)
+    );
+}
+
+#[test]
+fn tuple_intermediates_keep_declaration_order() {
+    let captured = CapturedSpecimen::from_new_specimen(MycologistId(2), NewSpecimen(3));
+    assert_eq!(captured, CapturedSpecimen(3, MycologistId(2)));
+    assert_eq!(
+        Specimen::from_captured_specimen(RustId(1), captured),
+        Specimen(RustId(1), 3, MycologistId(2))
+    );
+}
+
+#[test]
+fn unit_intermediates_are_unit() {
+    assert_eq!(NewSpore, NewSpore);
+}
@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_derive(Debug, "Clone")]
+#[intermediate_table_name("mycologists")]
+#[intermediate_ref(New)]
+struct Mycologist {
+    #[intermediate_exclude]
+    id: i32,
+    rust_count: i32,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_table_name(Captured = mikes, Reviewed = "reviewed_rusts")]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    mycologist_id: i32,
+}
+
+fn main() {}
@@ -0,0 +1,32 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_changeset(Captured)]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    mycologist_id: i32,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_changeset = "Patch"]
+struct Mycologist {
+    #[intermediate_exclude]
+    id: i32,
+    rust_count: i32,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_changeset(Patch)]
+#[intermediate_constructor(Patch = "patched")]
+enum Event {
+    Found {
+        #[intermediate_exclude]
+        id: i32,
+        rust_count: i32,
+    },
+}
+
+fn main() {}
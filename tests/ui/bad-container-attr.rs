@@ -0,0 +1,16 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_attr(Captured, doc = "captured")]
+#[intermediate_attr(Reviewed, doc = "reviewed")]
+#[intermediate_attr(New, )]
+#[intermediate_attr = "doc"]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    mycologist_id: i32,
+}
+
+fn main() {}
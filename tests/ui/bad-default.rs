@@ -0,0 +1,16 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+struct Rust {
+    #[intermediate_exclude(default = 0)]
+    id: i32,
+    #[intermediate_exclude(default = "now(")]
+    created_at: i32,
+    #[intermediate_exclude(default(0))]
+    version: i32,
+    #[intermediate_exclude(Captured, default, default = "1")]
+    mycologist_id: i32,
+}
+
+fn main() {}
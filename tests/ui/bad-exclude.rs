@@ -0,0 +1,19 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_derive(Debug)]
+struct Mycologist {
+    #[intermediate_exclude = "id"]
+    id: i32,
+    #[intermediate_exclude("Captured")]
+    rust_count: i32,
+    #[intermediate_exclude()]
+    name: String,
+    #[intermediate_exclude(Captured, Captured)]
+    location: String,
+    #[intermediate_exclude(New, Reviewed(yes))]
+    reviewer: String,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_changeset(Patch)]
+struct Rust {
+    #[intermediate_exclude]
+    #[intermediate_field_attr(New, doc = "the id")]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    #[intermediate_field_attr(Captured, doc = "who found it")]
+    #[intermediate_field_attr(Patch, doc = "who found it")]
+    mycologist_id: i32,
+    #[intermediate_field_attr(Reviewed, doc = "the stage")]
+    #[intermediate_field_attr(doc = "the stage")]
+    #[intermediate_field_attr(Patch, doc = "the stage")]
+    life_cycle_stage: i32,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_insert]
+#[intermediate_table_name = "rusts"]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_insert(backend = "oracle")]
+#[intermediate_table_name = "rusts"]
+struct Specimen {
+    #[intermediate_exclude]
+    id: i32,
+}
+
+fn main() {}
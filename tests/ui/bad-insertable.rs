@@ -0,0 +1,14 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_insertable(New, Captured, Reviewed)]
+#[intermediate_insertable = "New"]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    mycologist_id: i32,
+}
+
+fn main() {}
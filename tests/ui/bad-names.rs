@@ -0,0 +1,15 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_names(prefix, suffix, New = "Rust Insert", Capturd = "RustCaptured")]
+#[intermediate_names = "suffix"]
+#[intermediate_constructor(New = "from new", Reviewed = "reviewed")]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    mycologist_id: i32,
+}
+
+fn main() {}
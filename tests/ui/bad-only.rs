@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+struct Rust {
+    #[intermediate_exclude(Captured)]
+    #[intermediate_only(Captured)]
+    id: i32,
+    #[intermediate_only(New, Summary)]
+    mycologist_id: i32,
+    #[intermediate_only]
+    name: String,
+    #[intermediate_only("Summary")]
+    life_cycle_stage: i32,
+}
+
+fn main() {}
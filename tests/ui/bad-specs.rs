@@ -0,0 +1,26 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediates(New(exclude(id, created)), Captured(exclude(id, name)))]
+#[intermediates(Summary(include(id)), Summary(include(name)))]
+#[intermediates(Reviewed(only(id)))]
+struct Rust {
+    id: i32,
+    mycologist_id: i32,
+    name: String,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediates(New(include(id)))]
+struct Mycologist {
+    id: i32,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediates(New(exclude(id)))]
+enum Event {
+    Found { id: i32 },
+}
+
+fn main() {}
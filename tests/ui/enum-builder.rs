@@ -0,0 +1,14 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_builder]
+enum Event {
+    Found {
+        #[intermediate_exclude]
+        id: i32,
+        rust_count: i32,
+    },
+}
+
+fn main() {}
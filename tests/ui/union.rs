@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+union Event {
+    id: i32,
+    size: u32,
+}
+
+fn main() {}
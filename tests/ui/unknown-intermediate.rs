@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate diesel_derive_intermediate;
+
+#[derive(DieselIntermediate)]
+#[intermediate_derive(Debug, Capturd: Clone)]
+struct Rust {
+    #[intermediate_exclude]
+    id: i32,
+    #[intermediate_exclude(Captured)]
+    mycologist_id: i32,
+}
+
+#[derive(DieselIntermediate)]
+#[intermediate_derive(New: )]
+struct Mycologist {
+    #[intermediate_exclude]
+    id: i32,
+}
+
+fn main() {}